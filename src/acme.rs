@@ -0,0 +1,80 @@
+use crate::exclude::ACME_CHALLENGE_PREFIX;
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Answers ACME HTTP-01 challenges on behalf of the inner service.
+#[derive(Debug, Clone)]
+pub(crate) enum AcmeResponder {
+    /// Key authorizations by token, kept up to date by the application.
+    Tokens(Arc<RwLock<HashMap<String, String>>>),
+    /// A webroot directory, in which a client such as certbot stores key authorizations as
+    /// `.well-known/acme-challenge/<token>`.
+    Webroot(PathBuf),
+}
+
+impl AcmeResponder {
+    /// Returns the challenge token requested by the given path, if any.
+    pub(crate) fn token(path: &str) -> Option<&str> {
+        let token = path.strip_prefix(ACME_CHALLENGE_PREFIX)?;
+        // Tokens are base64url, which also rules out path traversal in the webroot.
+        let valid = !token.is_empty()
+            && token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        Some(token).filter(|_| valid)
+    }
+
+    /// Responds with the key authorization for the token, or `404 Not Found` if it is unknown.
+    pub(crate) async fn respond(self, token: String) -> HttpResponse {
+        let key_authorization = match self {
+            AcmeResponder::Tokens(tokens) => {
+                // A panic elsewhere while the application held the lock leaves the map usable.
+                let tokens = tokens.read().unwrap_or_else(PoisonError::into_inner);
+                tokens.get(&token).cloned()
+            }
+            AcmeResponder::Webroot(webroot) => {
+                let path = webroot
+                    .join(ACME_CHALLENGE_PREFIX.trim_start_matches('/'))
+                    .join(token);
+                web::block(move || std::fs::read_to_string(path))
+                    .await
+                    .ok()
+                    .and_then(Result::ok)
+            }
+        };
+        match key_authorization {
+            Some(key_authorization) => HttpResponse::Ok()
+                .content_type(ContentType::plaintext())
+                .body(key_authorization.trim().to_owned()),
+            None => HttpResponse::NotFound().finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    #[actix_web::test]
+    async fn poisoned_tokens_are_still_served() {
+        let tokens = Arc::new(RwLock::new(HashMap::new()));
+        tokens
+            .write()
+            .unwrap()
+            .insert("abc".to_owned(), "abc.key".to_owned());
+        let poisoner = Arc::clone(&tokens);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(tokens.is_poisoned());
+        let res = AcmeResponder::Tokens(tokens)
+            .respond("abc".to_owned())
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
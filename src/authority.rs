@@ -0,0 +1,417 @@
+use crate::replace::Replacements;
+use actix_web::middleware::TrailingSlash;
+use std::fmt::Write;
+
+/// Splits a host such as `example.com:8080` or `[::1]:8080` into its name and port.
+///
+/// Bracketed IPv6 literals keep their brackets, while a bare IPv6 literal such as `::1`, which
+/// some proxies send, is taken to have no port at all.
+pub(crate) fn split_port(host: &str) -> (&str, Option<&str>) {
+    if host.starts_with('[') {
+        return match host.find(']') {
+            Some(end) => match host[end + 1..].strip_prefix(':') {
+                Some(port) if is_port(port) => (&host[..=end], Some(port)),
+                _ => (&host[..=end], None),
+            },
+            None => (host, None),
+        };
+    }
+    match host.split_once(':') {
+        Some((name, port)) if is_port(port) => (name, Some(port)),
+        _ => (host, None),
+    }
+}
+
+/// Removes the userinfo, such as `user:pass@`, from an authority, so credentials sent in an
+/// absolute-form request are never reflected into the redirect.
+pub(crate) fn strip_userinfo(authority: &str) -> &str {
+    authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+}
+
+/// Joins a host name and port, bracketing bare IPv6 literals as required in urls.
+pub(crate) fn join_port(name: &str, port: Option<&str>) -> String {
+    let name = if name.contains(':') && !name.starts_with('[') {
+        format!("[{}]", name)
+    } else {
+        name.to_owned()
+    };
+    match port {
+        Some(port) => format!("{}:{}", name, port),
+        None => name,
+    }
+}
+
+/// Lowercases the name of a host and converts Unicode names to punycode, keeping the port.
+///
+/// Returns `None` if the name is not a valid domain name.
+pub(crate) fn normalize_host(host: &str) -> Option<String> {
+    let (name, port) = split_port(host);
+    let name = if name.starts_with('[') {
+        name.to_ascii_lowercase()
+    } else {
+        idna::domain_to_ascii(name).ok()?
+    };
+    Some(join_port(&name, port))
+}
+
+/// Returns whether a host name matches a pattern such as `example.com`, or `*.example.com` which
+/// matches any subdomain but not `example.com` itself. Both are compared case insensitively.
+pub(crate) fn host_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.to_ascii_lowercase(), name.to_ascii_lowercase());
+    match pattern.strip_prefix("*.") {
+        Some(domain) => name
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => name == pattern,
+    }
+}
+
+/// Replaces the name of a host, keeping its port unless the new name includes one.
+pub(crate) fn replace_name(host: &str, name: &str) -> String {
+    match split_port(host) {
+        (_, Some(port)) if split_port(name).1.is_none() => join_port(name, Some(port)),
+        _ => name.to_owned(),
+    }
+}
+
+/// Removes an explicit default port, such as `:443` for `https`, from the authority of a url.
+pub(crate) fn strip_default_port(url: &mut String, default_port: u16) {
+    let start = match url.find("://") {
+        Some(index) => index + 3,
+        None => return,
+    };
+    let end = url[start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |index| start + index);
+    if let (name, Some(port)) = split_port(&url[start..end]) {
+        if port.parse() == Ok(default_port) {
+            let name_end = start + name.len();
+            url.replace_range(name_end..end, "");
+        }
+    }
+}
+
+/// Percent-encodes every byte of `value` which `keep` rejects, and `%` itself if it does not
+/// start a valid escape sequence. Owned values which need no encoding are returned as they are,
+/// without copying.
+pub(crate) fn percent_encode<S>(value: S, keep: impl Fn(u8) -> bool) -> String
+where
+    S: AsRef<str> + Into<String>,
+{
+    let bytes = value.as_ref().as_bytes();
+    let is_kept = |i: usize, b: u8| {
+        let is_escape = b == b'%'
+            && bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+            && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit);
+        is_escape || (b != b'%' && keep(b))
+    };
+    if bytes.iter().enumerate().all(|(i, &b)| is_kept(i, b)) {
+        return value.into();
+    }
+    let mut encoded = String::with_capacity(bytes.len() + 8);
+    for (i, &b) in bytes.iter().enumerate() {
+        if is_kept(i, b) {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+/// Removes the parameters with the given names, compared case insensitively after decoding,
+/// from a query string. Returns `None` if no parameters remain.
+#[cfg(not(feature = "url"))]
+pub(crate) fn strip_query_params(query: &str, names: &[String]) -> Option<String> {
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or_default();
+            let name = percent_decode(&name.replace('+', " "));
+            !param.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(&name))
+        })
+        .collect();
+    match kept.is_empty() {
+        true => None,
+        false => Some(kept.join("&")),
+    }
+}
+
+/// Decodes the percent escape sequences of `value`, replacing invalid UTF-8.
+#[cfg(not(feature = "url"))]
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Collapses repeated slashes and resolves `.` and `..` segments of a path, including ones
+/// spelled with `%2e`, then trims, keeps or adds the trailing slash as `NormalizePath` would.
+pub(crate) fn normalize_path(path: &str, trailing_slash: TrailingSlash) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match dot_segment(segment) {
+            Some(1) => {}
+            Some(_) => {
+                segments.pop();
+            }
+            None if segment.is_empty() => {}
+            None => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    // A path ending in `.` or `..` refers to a directory, like one ending in a slash.
+    let has_trailing_slash = path
+        .rsplit('/')
+        .next()
+        .is_some_and(|last| last.is_empty() || dot_segment(last).is_some());
+    let add_trailing_slash = match trailing_slash {
+        TrailingSlash::Trim => false,
+        TrailingSlash::Always => true,
+        _ => has_trailing_slash,
+    };
+    if add_trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Returns the number of dots if the segment is `.` or `..`, with any of the dots encoded as
+/// `%2e`, which servers and browsers treat the same as a plain dot.
+fn dot_segment(segment: &str) -> Option<usize> {
+    let mut rest = segment.as_bytes();
+    let mut dots = 0;
+    while !rest.is_empty() && dots < 2 {
+        match rest {
+            [b'.', tail @ ..] => rest = tail,
+            [b'%', b'2', b'e' | b'E', tail @ ..] => rest = tail,
+            _ => return None,
+        }
+        dots += 1;
+    }
+    (rest.is_empty() && dots > 0).then_some(dots)
+}
+
+/// Returns whether a byte may appear as is in a path segment (RFC 3986 `pchar`) or as a
+/// separator between segments.
+pub(crate) fn is_path_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&b)
+}
+
+/// Returns whether a byte may appear as is anywhere in a url. Control characters, spaces,
+/// non-ASCII bytes and the characters RFC 3986 never allows are escaped, which also ensures
+/// the url cannot break out of the `Location` header.
+pub(crate) fn is_url_byte(b: u8) -> bool {
+    b.is_ascii_graphic() && !b"\"<>\\^`{|}".contains(&b)
+}
+
+fn is_port(port: &str) -> bool {
+    !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Replacements and port mappings which apply instead of the global ones when the request was
+/// made to a matching host.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostRule {
+    /// The host name, or a `*.` wildcard pattern, the rule applies to.
+    pub(crate) host: String,
+    pub(crate) replacements: Replacements,
+    pub(crate) ports: PortMapping,
+}
+
+/// Rewrites the port of the redirect url.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PortMapping {
+    /// Specific `(http, https)` port pairs.
+    pub(crate) map: Vec<(u16, u16)>,
+    /// The `https` port for any port not in `map`.
+    pub(crate) to: Option<u16>,
+}
+
+impl PortMapping {
+    /// Returns the port which `port` is mapped to, if any. A missing port is taken to be the
+    /// default port of the scheme the request was made over, such as 80 for `http`.
+    pub(crate) fn lookup(&self, port: Option<&str>, default_port: u16) -> Option<u16> {
+        let number = match port {
+            Some(port) => port.parse::<u16>().ok(),
+            None => Some(default_port),
+        };
+        number
+            .and_then(|number| self.map.iter().find(|(from, _)| *from == number))
+            .map(|(_, to)| *to)
+            .or(self.to)
+    }
+
+    /// Applies the mapping to the port of `host`. Hosts with unmapped ports are returned
+    /// unchanged.
+    pub(crate) fn apply(&self, host: String, default_port: u16) -> String {
+        let (name, port) = split_port(&host);
+        match self.lookup(port, default_port) {
+            Some(mapped) => join_port(name, Some(&mapped.to_string())),
+            None => join_port(name, port),
+        }
+    }
+}
+
+/// Compares two byte strings in time which depends only on their lengths, so secrets cannot be
+/// guessed one byte at a time.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_names_and_ports() {
+        assert_eq!(split_port("example.com"), ("example.com", None));
+        assert_eq!(
+            split_port("example.com:8080"),
+            ("example.com", Some("8080"))
+        );
+        assert_eq!(split_port("192.0.2.1:80"), ("192.0.2.1", Some("80")));
+    }
+
+    #[test]
+    fn splits_ipv6_literals() {
+        assert_eq!(split_port("[::1]"), ("[::1]", None));
+        assert_eq!(split_port("[::1]:8443"), ("[::1]", Some("8443")));
+        assert_eq!(split_port("[2001:db8::1]:"), ("[2001:db8::1]", None));
+        assert_eq!(split_port("::1"), ("::1", None));
+        assert_eq!(split_port("2001:db8::1"), ("2001:db8::1", None));
+        assert_eq!(split_port("[::1"), ("[::1", None));
+    }
+
+    #[test]
+    fn keeps_invalid_ports_as_part_of_the_name() {
+        assert_eq!(split_port(":"), (":", None));
+        assert_eq!(split_port("example.com:"), ("example.com:", None));
+        assert_eq!(split_port("example.com:http"), ("example.com:http", None));
+        assert_eq!(split_port("example.com:80a"), ("example.com:80a", None));
+        assert_eq!(split_port("[::1]:https"), ("[::1]", None));
+    }
+
+    #[test]
+    fn joins_names_and_ports() {
+        assert_eq!(join_port("::1", Some("8443")), "[::1]:8443");
+        assert_eq!(join_port("[::1]", None), "[::1]");
+        assert_eq!(join_port("example.com", Some("80")), "example.com:80");
+    }
+
+    /// Decodes every escape sequence, like a client following the redirect would.
+    fn decode(value: &str) -> Vec<u8> {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' {
+                decoded.push(u8::from_str_radix(&value[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            } else {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        // None of these contain valid escape sequences, which are kept rather than encoded.
+        for value in ["/a b/ü?q=\"<x>\"", "/100%/done", "\r\n\t\u{7f}", "/%zz%2"] {
+            let encoded = percent_encode(value, is_url_byte);
+            assert!(encoded.bytes().all(is_url_byte), "{:?}", encoded);
+            assert_eq!(decode(&encoded), value.as_bytes(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn percent_encoding_keeps_valid_escapes_and_reserved_bytes() {
+        assert_eq!(percent_encode("/a%2Fb%zz", is_url_byte), "/a%2Fb%25zz");
+        assert_eq!(
+            percent_encode("/p?a=1&b=[2]#f@!$'()*+,;=:", is_url_byte),
+            "/p?a=1&b=[2]#f@!$'()*+,;=:"
+        );
+        assert_eq!(percent_encode("/p?a#[b]", is_path_byte), "/p%3Fa%23%5Bb%5D");
+        assert_eq!(
+            percent_encode("<>\\^`{|} \"", is_url_byte),
+            "%3C%3E%5C%5E%60%7B%7C%7D%20%22"
+        );
+    }
+
+    #[test]
+    fn normalizes_dot_segments_and_repeated_slashes() {
+        for (path, expected) in [
+            ("/", "/"),
+            ("//a///b", "/a/b"),
+            ("/a/./b/../c", "/a/c"),
+            ("/../../a", "/a"),
+            ("/a/b/..", "/a/"),
+            ("/a/.", "/a/"),
+            ("/a/..b/.c/b..", "/a/..b/.c/b.."),
+            ("/..", "/"),
+        ] {
+            assert_eq!(
+                normalize_path(path, TrailingSlash::MergeOnly),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_encoded_dot_segments() {
+        for (path, expected) in [
+            ("/a/%2e/b", "/a/b"),
+            ("/a/b/%2e%2e/c", "/a/c"),
+            ("/a/b/%2E%2E/c", "/a/c"),
+            ("/a/b/.%2e/c", "/a/c"),
+            ("/a/b/%2e./c", "/a/c"),
+            ("/a/b/%2e%2e", "/a/"),
+            ("/a/%2e%2e%2e/b", "/a/%2e%2e%2e/b"),
+            ("/a/%2ex/b", "/a/%2ex/b"),
+            ("/a%2fb/%2e%2e", "/"),
+        ] {
+            assert_eq!(
+                normalize_path(path, TrailingSlash::MergeOnly),
+                expected,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn handles_the_trailing_slash() {
+        assert_eq!(normalize_path("/a/b/", TrailingSlash::Trim), "/a/b");
+        assert_eq!(normalize_path("/a/b/..", TrailingSlash::Trim), "/a");
+        assert_eq!(normalize_path("/a/b", TrailingSlash::Always), "/a/b/");
+        assert_eq!(normalize_path("/a/b//", TrailingSlash::MergeOnly), "/a/b/");
+        assert_eq!(normalize_path("/a/b", TrailingSlash::MergeOnly), "/a/b");
+        assert_eq!(normalize_path("//", TrailingSlash::Always), "/");
+    }
+}
@@ -0,0 +1,274 @@
+use actix_web::{dev::ServiceRequest, http::header, HttpResponse, HttpResponseBuilder};
+use std::sync::Arc;
+
+/// Renders a response body for a media type.
+pub(crate) type Renderer = Arc<dyn Fn(&RedirectTarget) -> String + Send + Sync>;
+
+/// The body sent along with a redirect.
+#[derive(Clone, Debug, Default)]
+pub(crate) enum Body {
+    #[default]
+    Empty,
+    /// Plain text rendered from a template.
+    Template(String),
+    /// A small HTML page linking to the redirect url.
+    Html,
+}
+
+impl Body {
+    /// Finishes the redirect response with this body.
+    pub(crate) fn respond(
+        &self,
+        mut res: HttpResponseBuilder,
+        target: &RedirectTarget,
+    ) -> HttpResponse {
+        match self {
+            Body::Empty => res.finish(),
+            Body::Template(template) => res
+                .content_type("text/plain; charset=utf-8")
+                .body(target.render(template)),
+            Body::Html => res
+                .content_type("text/html; charset=utf-8")
+                .body(html_page(target.url)),
+        }
+    }
+}
+
+/// Renderers for the bodies of responses to `http` requests, chosen by the `Accept` header.
+#[derive(Clone)]
+pub(crate) struct Negotiation {
+    renderers: Vec<(String, Renderer)>,
+}
+
+impl Default for Negotiation {
+    fn default() -> Self {
+        let text: Renderer = Arc::new(|target: &RedirectTarget| match target.message() {
+            Some(message) => format!("{}: {}", message, target.url()),
+            None => format!("Moved to {}", target.url()),
+        });
+        let html: Renderer = Arc::new(|target: &RedirectTarget| html_page(target.url()));
+        let json: Renderer =
+            Arc::new(|target: &RedirectTarget| json_error(target.url(), target.message()));
+        Negotiation {
+            renderers: vec![
+                ("text/plain".to_owned(), text),
+                ("text/html".to_owned(), html),
+                ("application/json".to_owned(), json),
+            ],
+        }
+    }
+}
+
+impl Negotiation {
+    /// Replaces the renderer for a media type, or adds it if there is none yet.
+    pub(crate) fn set(&mut self, media_type: &str, renderer: Renderer) {
+        let media_type = media_type.to_ascii_lowercase();
+        match self.renderers.iter_mut().find(|(ty, _)| *ty == media_type) {
+            Some((_, existing)) => *existing = renderer,
+            None => self.renderers.push((media_type, renderer)),
+        }
+    }
+
+    /// Finishes the response with the renderer the client prefers, or returns it unchanged if
+    /// the client accepts none of them.
+    pub(crate) fn respond(
+        &self,
+        req: &ServiceRequest,
+        mut res: HttpResponseBuilder,
+        target: &RedirectTarget,
+    ) -> Result<HttpResponse, HttpResponseBuilder> {
+        let ranges = media_ranges(req);
+        let quality = |media_type: &str| -> f32 {
+            if ranges.is_empty() {
+                return 1.0;
+            }
+            let (ty, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+            // The most specific matching range decides the quality.
+            ranges
+                .iter()
+                .filter_map(|(range, q)| match range.split_once('/') {
+                    _ if range.eq_ignore_ascii_case(media_type) => Some((2, *q)),
+                    Some((range_ty, "*")) if range_ty.eq_ignore_ascii_case(ty) => Some((1, *q)),
+                    Some(("*", "*")) => Some((0, *q)),
+                    _ => None,
+                })
+                .max_by_key(|(specificity, _)| *specificity)
+                .map_or(0.0, |(_, q)| q)
+        };
+        let mut best: Option<(f32, &(String, Renderer))> = None;
+        for renderer in &self.renderers {
+            let q = quality(&renderer.0);
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, renderer));
+            }
+        }
+        match best {
+            Some((_, (media_type, render))) => {
+                let content_type = match media_type.starts_with("text/") {
+                    true => format!("{}; charset=utf-8", media_type),
+                    false => media_type.clone(),
+                };
+                Ok(res.content_type(content_type).body(render(target)))
+            }
+            None => Err(res),
+        }
+    }
+}
+
+/// Renders a page which sends clients ignoring the `Location` header on to the url, both with a
+/// `<meta http-equiv="refresh">` and a link.
+fn html_page(url: &str) -> String {
+    let url = escape_html(url);
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"0; url={url}\">\n\
+         <title>Moved</title>\n\
+         </head>\n\
+         <body>\n\
+         <p>This page has moved to <a href=\"{url}\">{url}</a>.</p>\n\
+         </body>\n\
+         </html>\n",
+        url = url
+    )
+}
+
+/// Returns whether the client asked for `application/json`, so it gets a machine readable
+/// body explaining that `https` is required.
+pub(crate) fn accepts_json(req: &ServiceRequest) -> bool {
+    media_ranges(req)
+        .iter()
+        .any(|(range, q)| range.eq_ignore_ascii_case("application/json") && *q > 0.0)
+}
+
+/// Parses the media ranges of the `Accept` headers along with their quality values.
+fn media_ranges(req: &ServiceRequest) -> Vec<(&str, f32)> {
+    req.headers()
+        .get_all(header::ACCEPT)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| {
+            let mut params = media_range.split(';').map(str::trim);
+            let range = params.next().filter(|range| !range.is_empty())?;
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            Some((range, q))
+        })
+        .collect()
+}
+
+/// Renders the JSON body explaining that the request must be made to `location` over `https`.
+pub(crate) fn json_error(location: &str, message: Option<&str>) -> String {
+    let mut json = String::from("{\"error\":\"https_required\"");
+    if let Some(message) = message {
+        json.push_str(&format!(",\"message\":\"{}\"", escape_json(message)));
+    }
+    json.push_str(&format!(",\"location\":\"{}\"}}", escape_json(location)));
+    json
+}
+
+/// Escapes a value for use inside a JSON string.
+pub(crate) fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes the characters with a special meaning in HTML text and attribute values.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The redirect url, with its parts, which the body of a response to an `http` request is
+/// rendered from.
+pub struct RedirectTarget<'a> {
+    url: &'a str,
+    host: &'a str,
+    path: &'a str,
+    message: Option<&'a str>,
+}
+
+impl<'a> RedirectTarget<'a> {
+    /// Splits a redirect url such as `https://example.com/a?b` into its host and path.
+    pub(crate) fn new(url: &'a str) -> Self {
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        RedirectTarget {
+            url,
+            host: &rest[..end],
+            path: &rest[end..],
+            message: None,
+        }
+    }
+
+    /// Attaches the reason the request was rejected rather than redirected.
+    pub(crate) fn with_message(mut self, message: &'a str) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    /// The `https` url, such as `https://example.com/a?b`.
+    pub fn url(&self) -> &str {
+        self.url
+    }
+
+    /// The host of the url, such as `example.com`.
+    pub fn host(&self) -> &str {
+        self.host
+    }
+
+    /// The path of the url including the query string, such as `/a?b`.
+    pub fn path(&self) -> &str {
+        self.path
+    }
+
+    /// The reason the request was rejected, or `None` if it was redirected.
+    pub fn message(&self) -> Option<&str> {
+        self.message
+    }
+
+    /// Replaces the `{url}`, `{host}` and `{path}` placeholders of a template.
+    pub(crate) fn render(&self, template: &str) -> String {
+        let mut rendered = String::with_capacity(template.len() + self.url.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let placeholders = [
+                ("{url}", self.url),
+                ("{host}", self.host),
+                ("{path}", self.path),
+            ];
+            let (placeholder, value) = placeholders
+                .iter()
+                .find(|(placeholder, _)| rest.starts_with(placeholder))
+                .copied()
+                .unwrap_or(("{", "{"));
+            rendered.push_str(value);
+            rest = &rest[placeholder.len()..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
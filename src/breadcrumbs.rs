@@ -0,0 +1,22 @@
+//! Sentry breadcrumbs for plaintext traffic.
+
+use actix_web::dev::ServiceRequest;
+use sentry_core::protocol::{Breadcrumb, Map, Value};
+
+/// Adds a breadcrumb for an `http` request which was redirected or rejected, with its host, path
+/// and the decision.
+pub(crate) fn add(req: &ServiceRequest, host: Option<&str>, decision: &str) {
+    let mut data = Map::new();
+    if let Some(host) = host {
+        data.insert("host".to_owned(), Value::from(host));
+    }
+    data.insert("path".to_owned(), Value::from(req.path()));
+    data.insert("decision".to_owned(), Value::from(decision));
+    sentry_core::add_breadcrumb(Breadcrumb {
+        ty: "http".to_owned(),
+        category: Some("redirect_https".to_owned()),
+        message: Some(format!("{} {} over http", decision, req.path())),
+        data,
+        ..Default::default()
+    });
+}
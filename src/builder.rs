@@ -0,0 +1,66 @@
+use crate::{ConfigError, RedirectHTTPS};
+use std::marker::PhantomData;
+
+/// Marks a [`RedirectHTTPS`] whose options have not been validated yet, see
+/// [`RedirectHTTPSBuilder`].
+#[derive(Clone, Debug)]
+pub enum Unvalidated {}
+
+/// The builder returned by [`RedirectHTTPS::builder`]. It takes all the chainable options of
+/// [`RedirectHTTPS`], but is not a middleware itself: [`build`](Self::build) validates the
+/// options together, including the configurations of [`scope`](RedirectHTTPS::scope)s, and
+/// only then returns the middleware.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{App, http::StatusCode};
+/// use actix_web_middleware_redirect_https::RedirectHTTPS;
+///
+/// let redirect = RedirectHTTPS::builder()
+///     .status_code(StatusCode::PERMANENT_REDIRECT)
+///     .map_port(8080, 8443)
+///     .exclude_paths(&["/healthz"])
+///     .trust_x_forwarded_proto(true)
+///     .build()
+///     .unwrap();
+///
+/// App::new().wrap(redirect);
+/// ```
+///
+/// The builder can't be used as a middleware without being built:
+/// ```compile_fail
+/// use actix_web::App;
+/// use actix_web_middleware_redirect_https::RedirectHTTPS;
+///
+/// App::new().wrap(RedirectHTTPS::builder());
+/// ```
+pub type RedirectHTTPSBuilder = RedirectHTTPS<Unvalidated>;
+
+impl RedirectHTTPSBuilder {
+    /// Validates the options, returning the middleware if they make sense together. The options
+    /// of [`scope`](RedirectHTTPS::scope)s and the [`Hsts::path`](crate::Hsts::path) policies
+    /// are checked too. Invalid options set on a [`RedirectHTTPS`] directly are otherwise only
+    /// logged, with the `logging` feature, when the middleware starts.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::http::StatusCode;
+    /// use actix_web_middleware_redirect_https::{ConfigError, RedirectHTTPS};
+    ///
+    /// let invalid = RedirectHTTPS::builder().scope(
+    ///     "/api",
+    ///     RedirectHTTPS::default().status_code(StatusCode::OK),
+    /// );
+    /// assert_eq!(
+    ///     invalid.build().err(),
+    ///     Some(ConfigError::InvalidStatusCode(StatusCode::OK))
+    /// );
+    /// ```
+    pub fn build(self) -> Result<RedirectHTTPS, ConfigError> {
+        self.options.validate()?;
+        Ok(RedirectHTTPS {
+            options: self.options,
+            state: PhantomData,
+        })
+    }
+}
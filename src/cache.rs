@@ -0,0 +1,146 @@
+use actix_web::http::header::HeaderValue;
+use std::collections::{BTreeMap, HashMap};
+
+/// Everything about a request which the `Location` of its redirect depends on, for the options
+/// the cache was created with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub(crate) secure: bool,
+    pub(crate) websocket: bool,
+    pub(crate) host: Option<String>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) path_and_query: String,
+}
+
+/// A bounded cache of prebuilt `Location` values, which evicts the least recently used entry
+/// when full.
+#[derive(Debug)]
+pub(crate) struct LocationCache {
+    capacity: usize,
+    entries: HashMap<CacheKey, (HeaderValue, u64)>,
+    /// The keys of the entries by when they were last used.
+    order: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl LocationCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        LocationCache {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns the cached value for the key, marking it as recently used.
+    pub(crate) fn get(&mut self, key: &CacheKey) -> Option<HeaderValue> {
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.tick += 1;
+        let key = self.order.remove(last_used)?;
+        *last_used = self.tick;
+        self.order.insert(self.tick, key);
+        Some(value.clone())
+    }
+
+    /// Caches a value, evicting the least recently used entry if the cache is full.
+    pub(crate) fn insert(&mut self, key: CacheKey, value: HeaderValue) {
+        if self.capacity == 0 || self.entries.contains_key(&key) {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(path_and_query: &str) -> CacheKey {
+        CacheKey {
+            secure: false,
+            websocket: false,
+            host: Some("example.com".to_owned()),
+            prefix: None,
+            path_and_query: path_and_query.to_owned(),
+        }
+    }
+
+    fn value(location: &'static str) -> HeaderValue {
+        HeaderValue::from_static(location)
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let mut cache = LocationCache::new(2);
+        cache.insert(key("/a"), value("https://example.com/a"));
+        cache.insert(key("/b"), value("https://example.com/b"));
+        assert!(cache.get(&key("/a")).is_some());
+        cache.insert(key("/c"), value("https://example.com/c"));
+        assert_eq!(cache.get(&key("/a")), Some(value("https://example.com/a")));
+        assert_eq!(cache.get(&key("/b")), None);
+        assert_eq!(cache.get(&key("/c")), Some(value("https://example.com/c")));
+        cache.insert(key("/d"), value("https://example.com/d"));
+        assert_eq!(cache.get(&key("/a")), None);
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.order.len(), 2);
+    }
+
+    #[test]
+    fn inserting_an_existing_key_keeps_the_entry() {
+        let mut cache = LocationCache::new(1);
+        cache.insert(key("/a"), value("https://example.com/a"));
+        cache.insert(key("/a"), value("https://example.com/other"));
+        assert_eq!(cache.get(&key("/a")), Some(value("https://example.com/a")));
+        assert_eq!(cache.order.len(), 1);
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let mut cache = LocationCache::new(0);
+        cache.insert(key("/a"), value("https://example.com/a"));
+        assert_eq!(cache.get(&key("/a")), None);
+    }
+
+    #[test]
+    fn keys_differing_in_any_part_do_not_collide() {
+        let base = key("/a?b");
+        let variants = [
+            CacheKey {
+                secure: true,
+                ..base.clone()
+            },
+            CacheKey {
+                websocket: true,
+                ..base.clone()
+            },
+            CacheKey {
+                host: Some("example.com:8080".to_owned()),
+                ..base.clone()
+            },
+            CacheKey {
+                host: None,
+                ..base.clone()
+            },
+            CacheKey {
+                prefix: Some("/app".to_owned()),
+                ..base.clone()
+            },
+            key("/a?c"),
+            key("/a"),
+        ];
+        let mut cache = LocationCache::new(16);
+        cache.insert(base.clone(), value("https://example.com/a?b"));
+        for variant in &variants {
+            assert_eq!(cache.get(variant), None, "{:?}", variant);
+        }
+        assert!(cache.get(&base).is_some());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::replace::Replacements;
+use crate::{ConfigError, Hsts, RedirectHTTPS, SchemeSource};
+use actix_web::http::StatusCode;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Deserializable configuration for [`RedirectHTTPS`], so the middleware can be driven from an
+/// application config file. Every field is optional and keeps the default of the corresponding
+/// [`RedirectHTTPS`] option when missing. Create the middleware with
+/// [`RedirectHTTPS::from_config`].
+///
+/// ## Usage
+/// ```
+/// use actix_web::App;
+/// use actix_web_middleware_redirect_https::{RedirectConfig, RedirectHTTPS};
+///
+/// let config: RedirectConfig = serde_json::from_str(r#"{
+///     "replacements": [[":8080", ":8443"]],
+///     "status_code": 308,
+///     "exclude": ["/healthz"]
+/// }"#).unwrap();
+///
+/// App::new()
+///     .wrap(RedirectHTTPS::from_config(config).unwrap());
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct RedirectConfig {
+    /// See [`RedirectHTTPS::set_enabled`].
+    pub enabled: Option<bool>,
+    /// See [`RedirectHTTPS::with_replacements`].
+    pub replacements: Vec<(String, String)>,
+    /// See [`RedirectHTTPS::to_port`].
+    pub port: Option<u16>,
+    /// Pairs of `http` and `https` ports, see [`RedirectHTTPS::map_port`].
+    pub port_map: Vec<(u16, u16)>,
+    /// See [`RedirectHTTPS::canonical_host`].
+    pub canonical_host: Option<String>,
+    /// See [`RedirectHTTPS::allowed_hosts`].
+    pub allowed_hosts: Vec<String>,
+    /// See [`RedirectHTTPS::fallback_host`].
+    pub fallback_host: Option<String>,
+    /// See [`RedirectHTTPS::status_code`].
+    pub status_code: Option<u16>,
+    /// See [`RedirectHTTPS::preserve_method`].
+    pub preserve_method: Option<bool>,
+    /// The `max-age` of the HSTS header in seconds, see [`RedirectHTTPS::hsts`].
+    pub hsts_max_age: Option<u64>,
+    /// See [`Hsts::include_subdomains`]. Requires `hsts_max_age`.
+    pub hsts_include_subdomains: Option<bool>,
+    /// See [`Hsts::preload`]. Requires `hsts_max_age`.
+    pub hsts_preload: Option<bool>,
+    /// Path prefixes, see [`RedirectHTTPS::exclude_paths`].
+    pub exclude: Vec<String>,
+    /// See [`RedirectHTTPS::allow_acme_challenges`].
+    pub allow_acme_challenges: Option<bool>,
+    /// See [`RedirectHTTPS::scheme_sources`].
+    pub scheme_sources: Option<Vec<SchemeSource>>,
+    /// See [`RedirectHTTPS::trusted_proxies`].
+    pub trusted_proxies: Vec<IpNet>,
+    /// See [`RedirectHTTPS::exempt_peers`].
+    pub exempt_peers: Vec<IpNet>,
+}
+
+impl RedirectConfig {
+    /// Builds the middleware, checking the options with [`RedirectHTTPSBuilder::build`](crate::RedirectHTTPSBuilder::build).
+    pub(crate) fn build(self) -> Result<RedirectHTTPS, ConfigError> {
+        let mut builder = RedirectHTTPS::builder()
+            .trusted_proxies(&self.trusted_proxies)
+            .exempt_peers(&self.exempt_peers);
+        builder.options_mut().replacements = Replacements::new(&self.replacements);
+        if let Some(enabled) = self.enabled {
+            builder = builder.set_enabled(enabled);
+        }
+        if let Some(port) = self.port {
+            builder = builder.to_port(port);
+        }
+        for (from, to) in self.port_map {
+            builder = builder.map_port(from, to);
+        }
+        if let Some(host) = &self.canonical_host {
+            builder = builder.canonical_host(host);
+        }
+        if !self.allowed_hosts.is_empty() {
+            let hosts: Vec<&str> = self.allowed_hosts.iter().map(String::as_str).collect();
+            builder = builder.allowed_hosts(&hosts);
+        }
+        if let Some(host) = &self.fallback_host {
+            builder = builder.fallback_host(host);
+        }
+        if let Some(status_code) = self.status_code {
+            let status_code =
+                StatusCode::from_u16(status_code).map_err(|_| ConfigError::InvalidValue {
+                    name: "status_code".to_owned(),
+                    value: status_code.to_string(),
+                })?;
+            builder = builder.status_code(status_code);
+        }
+        if let Some(preserve_method) = self.preserve_method {
+            builder = builder.preserve_method(preserve_method);
+        }
+        if let Some(max_age) = self.hsts_max_age {
+            let hsts = Hsts::new(Duration::from_secs(max_age))
+                .include_subdomains(self.hsts_include_subdomains.unwrap_or(false))
+                .preload(self.hsts_preload.unwrap_or(false));
+            builder = builder.hsts_policy(hsts);
+        } else {
+            // Without a `max-age` there is no header to add the directives to.
+            let directives = [
+                ("hsts_include_subdomains", self.hsts_include_subdomains),
+                ("hsts_preload", self.hsts_preload),
+            ];
+            if let Some((name, _)) = directives.iter().find(|(_, set)| *set == Some(true)) {
+                return Err(ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value: "true".to_owned(),
+                });
+            }
+        }
+        if !self.exclude.is_empty() {
+            let paths: Vec<&str> = self.exclude.iter().map(String::as_str).collect();
+            builder = builder.exclude_paths(&paths);
+        }
+        if let Some(allow) = self.allow_acme_challenges {
+            builder = builder.allow_acme_challenges(allow);
+        }
+        if let Some(sources) = &self.scheme_sources {
+            builder = builder.scheme_sources(sources);
+        }
+        builder.build()
+    }
+}
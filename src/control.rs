@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A cloneable handle to turn redirection on and off at runtime, for example from an admin route
+/// or a signal handler, without restarting workers. Attach it with
+/// [`RedirectHTTPS::control`](crate::RedirectHTTPS::control); all clones share the same state.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{App, web, HttpResponse};
+/// use actix_web_middleware_redirect_https::{RedirectControl, RedirectHTTPS};
+///
+/// let control = RedirectControl::new(true);
+///
+/// App::new()
+///     .app_data(web::Data::new(control.clone()))
+///     .wrap(RedirectHTTPS::default().control(&control))
+///     .route("/admin/redirect/off", web::post().to(|control: web::Data<RedirectControl>| async move {
+///         control.set_enabled(false);
+///         HttpResponse::NoContent().finish()
+///     }));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedirectControl {
+    enabled: Arc<AtomicBool>,
+}
+
+impl RedirectControl {
+    /// Creates a handle, with redirection initially enabled or disabled.
+    pub fn new(enabled: bool) -> Self {
+        RedirectControl {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+        }
+    }
+
+    /// Turns redirection on or off for every middleware attached to this handle.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether redirection is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for RedirectControl {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
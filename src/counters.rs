@@ -0,0 +1,16 @@
+//! Counters for the `metrics` facade, picked up by whichever exporter the application installs.
+
+/// Counts a redirect.
+pub(crate) fn redirect() {
+    metrics::counter!("https_redirects_total").increment(1);
+}
+
+/// Counts an `http` request which was passed on to the application, such as an exempt one.
+pub(crate) fn pass() {
+    metrics::counter!("http_passthroughs_total").increment(1);
+}
+
+/// Counts an `http` request which was rejected because of its method.
+pub(crate) fn rejection() {
+    metrics::counter!("insecure_post_rejections_total").increment(1);
+}
@@ -0,0 +1,154 @@
+use crate::replace::Replacements;
+use crate::{ConfigError, RedirectHTTPS};
+use actix_web::http::StatusCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Parses a boolean such as `true`, `0`, `yes` or `off`.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses comma separated replacements such as `:8080->:8443,old.com->new.com`.
+pub(crate) fn parse_replacements(value: &str) -> Option<Vec<(String, String)>> {
+    split_list(value)
+        .map(|replacement| {
+            let (from, to) = replacement.split_once("->")?;
+            Some((from.to_owned(), to.to_owned()))
+        })
+        .collect()
+}
+
+/// Parses a redirection status code such as `308`.
+pub(crate) fn parse_status_code(value: &str) -> Option<StatusCode> {
+    let status_code = value.trim().parse::<u16>().ok()?;
+    StatusCode::from_u16(status_code)
+        .ok()
+        .filter(StatusCode::is_redirection)
+}
+
+/// Splits a comma separated list, ignoring empty entries.
+pub(crate) fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// The variable which, when set to a false value such as `0`, disables the middleware however it
+/// was configured.
+pub(crate) const KILL_SWITCH: &str = "FORCE_HTTPS";
+
+/// Returns whether the kill switch in the value of [`KILL_SWITCH`] disables the middleware.
+/// Values which are not booleans are ignored, so a typo cannot turn redirection off.
+pub(crate) fn is_killed(value: Option<String>) -> bool {
+    value.and_then(|value| parse_bool(&value)) == Some(false)
+}
+
+/// Configures the middleware from the variables returned by `var`.
+pub(crate) fn from_vars<F>(var: F) -> Result<RedirectHTTPS, ConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let invalid = |name: &str, value: &str| ConfigError::InvalidValue {
+        name: name.to_owned(),
+        value: value.to_owned(),
+    };
+    let mut config = RedirectHTTPS::default();
+    if let Some(value) = var("REDIRECT_HTTPS_ENABLED") {
+        let enabled =
+            parse_bool(&value).ok_or_else(|| invalid("REDIRECT_HTTPS_ENABLED", &value))?;
+        config = config.set_enabled(enabled);
+    }
+    if let Some(value) = var("REDIRECT_HTTPS_PORT") {
+        let port = value
+            .trim()
+            .parse()
+            .map_err(|_| invalid("REDIRECT_HTTPS_PORT", &value))?;
+        config = config.to_port(port);
+    }
+    if let Some(value) = var("REDIRECT_HTTPS_REPLACEMENTS") {
+        let replacements = parse_replacements(&value)
+            .ok_or_else(|| invalid("REDIRECT_HTTPS_REPLACEMENTS", &value))?;
+        config.options_mut().replacements = Replacements::new(&replacements);
+    }
+    if let Some(value) = var("REDIRECT_HTTPS_STATUS") {
+        let status_code =
+            parse_status_code(&value).ok_or_else(|| invalid("REDIRECT_HTTPS_STATUS", &value))?;
+        config = config.status_code(status_code);
+    }
+    if let Some(value) = var("REDIRECT_HTTPS_EXCLUDE") {
+        let paths: Vec<&str> = split_list(&value).collect();
+        config = config.exclude_paths(&paths);
+    }
+    Ok(config)
+}
+
+/// Parses a compact configuration such as `status=308;map=:8080->:8443;exclude=/healthz`.
+///
+/// Options are separated by `;`, and may be repeated or given comma separated values where that
+/// makes sense:
+///
+/// - `enabled=false`: see [`RedirectHTTPS::set_enabled`].
+/// - `status=308`: see [`RedirectHTTPS::status_code`].
+/// - `preserve_method=true`: see [`RedirectHTTPS::preserve_method`].
+/// - `map=:8080->:8443`: string replacements, see [`RedirectHTTPS::with_replacements`].
+/// - `port=8443`: see [`RedirectHTTPS::to_port`].
+/// - `canonical=example.com`: see [`RedirectHTTPS::canonical_host`].
+/// - `hsts=31536000`: the `max-age` in seconds, see [`RedirectHTTPS::hsts`].
+/// - `exclude=/healthz,/metrics`: see [`RedirectHTTPS::exclude_paths`].
+/// - `acme=true`: see [`RedirectHTTPS::allow_acme_challenges`].
+///
+/// ## Usage
+/// ```
+/// use actix_web::App;
+/// use actix_web_middleware_redirect_https::RedirectHTTPS;
+///
+/// let config: RedirectHTTPS = "status=308;map=:8080->:8443;exclude=/healthz".parse().unwrap();
+/// App::new()
+///     .wrap(config);
+/// ```
+impl FromStr for RedirectHTTPS {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = RedirectHTTPS::default();
+        for option in s
+            .split(';')
+            .map(str::trim)
+            .filter(|option| !option.is_empty())
+        {
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            let (name, value) = (name.trim(), value.trim());
+            let invalid = || ConfigError::InvalidValue {
+                name: name.to_owned(),
+                value: value.to_owned(),
+            };
+            config = match name {
+                "enabled" => config.set_enabled(parse_bool(value).ok_or_else(invalid)?),
+                "status" => config.status_code(parse_status_code(value).ok_or_else(invalid)?),
+                "preserve_method" => config.preserve_method(parse_bool(value).ok_or_else(invalid)?),
+                "map" => {
+                    let replacements = parse_replacements(value).ok_or_else(invalid)?;
+                    config.options_mut().replacements.extend(&replacements);
+                    config
+                }
+                "port" => config.to_port(value.parse().map_err(|_| invalid())?),
+                "canonical" if value.is_empty() => return Err(invalid()),
+                "canonical" => config.canonical_host(value),
+                "hsts" => {
+                    let max_age = value.parse().map_err(|_| invalid())?;
+                    config.hsts(Duration::from_secs(max_age))
+                }
+                "exclude" => config.exclude_paths(&split_list(value).collect::<Vec<_>>()),
+                "acme" => config.allow_acme_challenges(parse_bool(value).ok_or_else(invalid)?),
+                _ => return Err(ConfigError::UnknownOption(name.to_owned())),
+            };
+        }
+        Ok(config)
+    }
+}
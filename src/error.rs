@@ -0,0 +1,64 @@
+use crate::Scheme;
+use actix_web::http::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+/// Error returned when the middleware is given an invalid combination of options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The redirect status code is not a redirection (`3xx`) status code.
+    InvalidStatusCode(StatusCode),
+    /// The status code for rejected unsafe methods is not a client error (`4xx`) status code.
+    InvalidRejectStatusCode(StatusCode),
+    /// An option read from the environment or a configuration has an invalid value.
+    InvalidValue {
+        /// The name of the environment variable or option.
+        name: String,
+        /// The value which could not be parsed.
+        value: String,
+    },
+    /// Requests would be redirected to the scheme they were made over.
+    SameScheme(Scheme),
+    /// A location template has an unknown or unclosed placeholder.
+    InvalidTemplate(String),
+    /// A configuration string contains an option which does not exist.
+    UnknownOption(String),
+    /// HSTS preloading requires a `max-age` of at least one year.
+    HstsPreloadMaxAge(Duration),
+    /// HSTS preloading requires the `includeSubDomains` directive.
+    HstsPreloadSubdomains,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidStatusCode(status_code) => {
+                write!(f, "redirect status code must be 3xx, got {}", status_code)
+            }
+            ConfigError::InvalidRejectStatusCode(status_code) => {
+                write!(f, "rejection status code must be 4xx, got {}", status_code)
+            }
+            ConfigError::InvalidValue { name, value } => {
+                write!(f, "invalid value for {}: {:?}", name, value)
+            }
+            ConfigError::SameScheme(scheme) => {
+                write!(f, "cannot redirect from {} to itself", scheme.as_str())
+            }
+            ConfigError::InvalidTemplate(template) => {
+                write!(f, "invalid location template {:?}", template)
+            }
+            ConfigError::UnknownOption(name) => write!(f, "unknown option {:?}", name),
+            ConfigError::HstsPreloadMaxAge(max_age) => write!(
+                f,
+                "HSTS preload requires a max-age of at least 31536000 seconds, got {}",
+                max_age.as_secs()
+            ),
+            ConfigError::HstsPreloadSubdomains => {
+                write!(f, "HSTS preload requires includeSubDomains")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,261 @@
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+/// The path under which ACME HTTP-01 challenge tokens are requested.
+pub(crate) const ACME_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// How [`RedirectHTTPS::health_checks`](crate::RedirectHTTPS::health_checks) serves
+/// health-check paths over `http`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum HealthCheckPolicy {
+    /// Passes health checks through to the application, which answers them itself.
+    #[default]
+    PassThrough,
+    /// Answers health checks with an empty `200 OK` from the middleware, without calling the
+    /// application.
+    Respond,
+}
+
+/// Paths which are served over `http` instead of being redirected.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exclusions {
+    prefixes: Vec<String>,
+    health_checks: Vec<(String, HealthCheckPolicy)>,
+    #[cfg(feature = "regex")]
+    regexes: Vec<Regex>,
+    globs: Vec<Glob>,
+    pub(crate) acme_challenges: bool,
+}
+
+impl Exclusions {
+    pub(crate) fn add_prefixes(&mut self, prefixes: &[&str]) {
+        self.prefixes
+            .extend(prefixes.iter().map(|prefix| prefix.to_string()));
+    }
+
+    pub(crate) fn add_health_checks(&mut self, paths: &[&str], policy: HealthCheckPolicy) {
+        self.health_checks
+            .extend(paths.iter().map(|path| (path.to_string(), policy)));
+    }
+
+    #[cfg(feature = "regex")]
+    pub(crate) fn add_regex(&mut self, regex: Regex) {
+        self.regexes.push(regex);
+    }
+
+    pub(crate) fn add_glob(&mut self, pattern: &str) {
+        self.globs.push(Glob::new(pattern));
+    }
+
+    /// Returns how the given request path is served if it is a health check, which must match
+    /// exactly.
+    pub(crate) fn health_check(&self, path: &str) -> Option<HealthCheckPolicy> {
+        self.health_checks
+            .iter()
+            .find(|(health_check, _)| health_check == path)
+            .map(|(_, policy)| *policy)
+    }
+
+    /// Returns whether the given request path should not be redirected.
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        if self.acme_challenges && matches_prefix(ACME_CHALLENGE_PREFIX, path) {
+            return true;
+        }
+        if self
+            .prefixes
+            .iter()
+            .any(|prefix| matches_prefix(prefix, path))
+        {
+            return true;
+        }
+        if self.globs.iter().any(|glob| glob.matches(path)) {
+            return true;
+        }
+        #[cfg(feature = "regex")]
+        if self.regexes.iter().any(|regex| regex.is_match(path)) {
+            return true;
+        }
+        false
+    }
+}
+
+/// Returns whether `path` is `prefix` or lies below it. `/healthz` matches `/healthz` and
+/// `/healthz/db` but not `/healthzfoo`, while `/internal/` matches anything starting with it.
+pub(crate) fn matches_prefix(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// A compiled glob pattern such as `/static/**/*.map`.
+#[derive(Debug, Clone)]
+struct Glob {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`, any single character except `/`.
+    AnyChar,
+    /// `*`, any sequence of characters except `/`.
+    Star,
+    /// `**`, any sequence of characters.
+    Globstar,
+    /// `**/` at the start of a segment, zero or more whole segments.
+    GlobstarDir,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        let mut segment_start = true;
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '?' => Token::AnyChar,
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if segment_start && chars.peek() == Some(&'/') {
+                        chars.next();
+                        Token::GlobstarDir
+                    } else {
+                        Token::Globstar
+                    }
+                }
+                '*' => Token::Star,
+                c => Token::Char(c),
+            };
+            segment_start = matches!(token, Token::Char('/') | Token::GlobstarDir);
+            tokens.push(token);
+        }
+        Glob { tokens }
+    }
+
+    /// Matches by tracking every token the path could have reached at once, rather than by
+    /// backtracking, so patterns with many wildcards take time linear in the length of the path.
+    fn matches(&self, path: &str) -> bool {
+        let len = self.tokens.len();
+        // `active[i]` means the path so far matches the tokens before `i`, and `pending[i]` that
+        // it is partway through a segment matched by the `**/` at `i`.
+        let mut active = vec![false; len + 1];
+        let mut pending = vec![false; len];
+        active[0] = true;
+        self.skip_empty(&mut active);
+        for c in path.chars() {
+            let mut next_active = vec![false; len + 1];
+            let mut next_pending = vec![false; len];
+            for (i, token) in self.tokens.iter().enumerate() {
+                if active[i] {
+                    match *token {
+                        Token::Char(expected) if c == expected => next_active[i + 1] = true,
+                        Token::AnyChar if c != '/' => next_active[i + 1] = true,
+                        Token::Star if c != '/' => next_active[i] = true,
+                        Token::Globstar => next_active[i] = true,
+                        Token::GlobstarDir if c == '/' => next_active[i] = true,
+                        Token::GlobstarDir => next_pending[i] = true,
+                        _ => {}
+                    }
+                }
+                if pending[i] {
+                    if c == '/' {
+                        next_active[i] = true;
+                    } else {
+                        next_pending[i] = true;
+                    }
+                }
+            }
+            self.skip_empty(&mut next_active);
+            if !next_active.contains(&true) && !next_pending.contains(&true) {
+                return false;
+            }
+            active = next_active;
+            pending = next_pending;
+        }
+        active[len]
+    }
+
+    /// Marks the tokens after wildcards which may match nothing as reached too.
+    fn skip_empty(&self, active: &mut [bool]) {
+        for (i, token) in self.tokens.iter().enumerate() {
+            if active[i] && matches!(token, Token::Star | Token::Globstar | Token::GlobstarDir) {
+                active[i + 1] = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).matches(path)
+    }
+
+    #[test]
+    fn star_stays_within_a_segment() {
+        assert!(matches("/static/*.js", "/static/app.js"));
+        assert!(matches("/static/*.js", "/static/.js"));
+        assert!(!matches("/static/*.js", "/static/js/app.js"));
+        assert!(matches("/*/*", "/a/b"));
+        assert!(!matches("/*/*", "/a/b/c"));
+        assert!(matches("/a*", "/a"));
+        assert!(matches("/*", "/"));
+        assert!(!matches("/*", "//"));
+    }
+
+    #[test]
+    fn globstar_crosses_segments() {
+        assert!(matches("/static/**", "/static/"));
+        assert!(matches("/static/**", "/static/a/b/c.js"));
+        assert!(!matches("/static/**", "/static"));
+        assert!(matches("/a**b", "/a/x/b"));
+        assert!(matches("/a**b", "/ab"));
+        assert!(matches("**", ""));
+    }
+
+    #[test]
+    fn globstar_dir_matches_zero_or_more_segments() {
+        assert!(matches("/static/**/*.map", "/static/app.map"));
+        assert!(matches("/static/**/*.map", "/static/js/app.map"));
+        assert!(matches("/static/**/*.map", "/static/js/vendor/app.map"));
+        assert!(!matches("/static/**/*.map", "/static/js/app.js"));
+        assert!(!matches("/static/**/*.map", "/staticx/app.map"));
+        assert!(matches("**/x", "x"));
+        assert!(matches("**/x", "/a/x"));
+        assert!(!matches("**/x", "/ax"));
+    }
+
+    #[test]
+    fn any_char_matches_one_character_except_slash() {
+        assert!(matches("/v?/api", "/v1/api"));
+        assert!(matches("/v?/api", "/vé/api"));
+        assert!(!matches("/v?/api", "/v/api"));
+        assert!(!matches("/v?/api", "/v12/api"));
+        assert!(!matches("/v?", "/v/"));
+    }
+
+    #[test]
+    fn patterns_match_the_whole_path() {
+        assert!(matches("/exact", "/exact"));
+        assert!(!matches("/exact", "/exact/"));
+        assert!(!matches("/exact", "/exac"));
+        assert!(!matches("", "/"));
+        assert!(matches("", ""));
+    }
+
+    #[test]
+    fn many_wildcards_do_not_backtrack() {
+        let pattern = format!("{}b", "*a".repeat(30));
+        let path = "a".repeat(100);
+        assert!(!matches(&pattern, &path));
+        assert!(matches(&pattern, &format!("{}b", path)));
+        let pattern = format!("/{}x", "**/".repeat(30));
+        let path = "/a".repeat(100);
+        assert!(!matches(&pattern, &path));
+        assert!(matches(&pattern, &format!("{}/x", path)));
+    }
+}
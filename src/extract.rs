@@ -0,0 +1,134 @@
+use crate::authority::{is_url_byte, percent_encode};
+use crate::skip::Handled;
+use crate::{is_valid_host, ConfigError};
+use actix_web::{
+    dev::Payload,
+    error::{ErrorForbidden, InternalError},
+    http::{header, StatusCode},
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures::future::{ready, Ready};
+use std::sync::Arc;
+
+/// Builds the error [`RequireHttps`] rejects a request with.
+type ErrorHandler = Arc<dyn Fn(&HttpRequest) -> Error + Send + Sync>;
+
+/// An extractor which rejects requests not made over `https`, for enforcing it on a few
+/// sensitive handlers without wrapping the whole application.
+///
+/// Requests are rejected with `403 Forbidden` unless a [`RequireHttpsConfig`] in the app data
+/// says otherwise. Behind [`RedirectHTTPS`](crate::RedirectHTTPS), the extractor agrees with the
+/// middleware about the scheme, including its trusted proxies. Without it, only the listener
+/// counts, according to `req.app_config().secure()`, since forwarded headers could come from
+/// any client.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{web, App, HttpResponse};
+/// use actix_web_middleware_redirect_https::RequireHttps;
+///
+/// async fn account(_: RequireHttps) -> HttpResponse {
+///     HttpResponse::Ok().finish()
+/// }
+///
+/// App::new().route("/account", web::get().to(account));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RequireHttps;
+
+/// Configures how [`RequireHttps`] rejects requests, when added to the app data.
+#[derive(Clone, Default)]
+pub struct RequireHttpsConfig {
+    error_handler: Option<ErrorHandler>,
+    /// The host and status code to redirect with.
+    redirect: Option<(String, StatusCode)>,
+}
+
+impl RequireHttpsConfig {
+    /// Rejects requests with the error returned by `handler`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{error, App};
+    /// use actix_web_middleware_redirect_https::RequireHttpsConfig;
+    ///
+    /// App::new().app_data(
+    ///     RequireHttpsConfig::default()
+    ///         .error_handler(|_| error::ErrorNotFound("not found")),
+    /// );
+    /// ```
+    pub fn error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Error + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Redirects requests to the same path and query on `host` over `https`, with the given
+    /// status code, instead of rejecting them. The host is fixed rather than taken from the
+    /// request, so clients cannot turn the redirect towards another site.
+    ///
+    /// Returns an error if the status code is not a redirection (`3xx`) status code or the host
+    /// is not valid.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{http::StatusCode, App};
+    /// use actix_web_middleware_redirect_https::RequireHttpsConfig;
+    ///
+    /// let config = RequireHttpsConfig::default()
+    ///     .redirect("example.com", StatusCode::TEMPORARY_REDIRECT)
+    ///     .unwrap();
+    /// App::new().app_data(config);
+    /// ```
+    pub fn redirect(mut self, host: &str, status_code: StatusCode) -> Result<Self, ConfigError> {
+        if !status_code.is_redirection() {
+            return Err(ConfigError::InvalidStatusCode(status_code));
+        }
+        if !is_valid_host(host) {
+            return Err(ConfigError::InvalidValue {
+                name: "host".to_owned(),
+                value: host.to_owned(),
+            });
+        }
+        self.redirect = Some((host.to_owned(), status_code));
+        Ok(self)
+    }
+
+    /// Returns the error to reject an `http` request with.
+    fn reject(&self, req: &HttpRequest) -> Error {
+        if let Some((host, status_code)) = &self.redirect {
+            let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+            let url = format!("https://{}{}", host, path);
+            let res = HttpResponse::build(*status_code)
+                .insert_header((header::LOCATION, percent_encode(url, is_url_byte)))
+                .finish();
+            return InternalError::from_response("https is required", res).into();
+        }
+        match &self.error_handler {
+            Some(handler) => handler(req),
+            None => ErrorForbidden("https is required"),
+        }
+    }
+}
+
+impl FromRequest for RequireHttps {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let handled = req
+            .extensions()
+            .get::<Handled>()
+            .map(|handled| handled.secure);
+        if handled.unwrap_or_else(|| req.app_config().secure()) {
+            return ready(Ok(RequireHttps));
+        }
+        let error = match req.app_data::<RequireHttpsConfig>() {
+            Some(config) => config.reject(req),
+            None => RequireHttpsConfig::default().reject(req),
+        };
+        ready(Err(error))
+    }
+}
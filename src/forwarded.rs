@@ -0,0 +1,684 @@
+use crate::authority::{join_port, split_port};
+use actix_web::{
+    dev::{RequestHead, ServiceRequest},
+    http::header::{self, HeaderName, HeaderValue},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+const X_FORWARDED_PORT: HeaderName = HeaderName::from_static("x-forwarded-port");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PREFIX: HeaderName = HeaderName::from_static("x-forwarded-prefix");
+const CF_VISITOR: HeaderName = HeaderName::from_static("cf-visitor");
+const X_ARR_SSL: HeaderName = HeaderName::from_static("x-arr-ssl");
+const FRONT_END_HTTPS: HeaderName = HeaderName::from_static("front-end-https");
+
+/// A request header which a proxy may use to tell us the scheme the client originally used, for
+/// use with [`RedirectHTTPS::scheme_sources`](crate::RedirectHTTPS::scheme_sources).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum SchemeSource {
+    /// `X-Forwarded-Proto: https`
+    XForwardedProto,
+    /// `Forwarded: proto=https` as specified by RFC 7239.
+    Forwarded,
+    /// `CF-Visitor: {"scheme":"https"}` as sent by Cloudflare.
+    CfVisitor,
+    /// `X-ARR-SSL`, which Azure App Service and IIS Application Request Routing only send for
+    /// `https` requests.
+    XArrSsl,
+    /// `Front-End-Https: on` as sent by IIS and some older proxies.
+    FrontEndHttps,
+    /// Not a header, but whether the listener the request arrived on uses TLS, according to
+    /// `req.app_config().secure()`, which is also the fallback when no source applies. This always
+    /// decides, so any sources after it are ignored. Useful when `http` and `https` listeners are
+    /// bound to separate `App`s.
+    AppConfig,
+}
+
+#[cfg(feature = "tracing")]
+impl SchemeSource {
+    /// Returns the name of the source, as written in configuration files.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SchemeSource::XForwardedProto => "x_forwarded_proto",
+            SchemeSource::Forwarded => "forwarded",
+            SchemeSource::CfVisitor => "cf_visitor",
+            SchemeSource::XArrSsl => "x_arr_ssl",
+            SchemeSource::FrontEndHttps => "front_end_https",
+            SchemeSource::AppConfig => "app_config",
+        }
+    }
+}
+
+/// Determines how the middleware works out the original scheme of a request.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Forwarding {
+    /// The sources to consult in order, or `None` to use actix's `ConnectionInfo`.
+    sources: Option<Vec<SchemeSource>>,
+    /// The proxies whose headers are believed, or empty to believe any peer.
+    pub(crate) trusted_proxies: Vec<IpNet>,
+    /// The number of proxies which append to forwarded headers, or `None` to use the first value.
+    pub(crate) hops: Option<usize>,
+    /// Whether the port of the redirect is taken from `X-Forwarded-Port`.
+    pub(crate) x_forwarded_port: bool,
+    /// Whether the host of the redirect is taken from `X-Forwarded-Host`, or `None` to decide
+    /// along with the scheme.
+    pub(crate) x_forwarded_host: Option<bool>,
+    /// Whether the redirect path is prefixed with `X-Forwarded-Prefix`.
+    pub(crate) x_forwarded_prefix: bool,
+}
+
+impl Forwarding {
+    /// Replaces the trusted sources.
+    pub(crate) fn set_sources(&mut self, sources: &[SchemeSource]) {
+        self.sources = Some(sources.to_vec());
+    }
+
+    /// Enables or disables trust in the given source.
+    pub(crate) fn set_source(&mut self, source: SchemeSource, trusted: bool) {
+        let sources = self.sources.get_or_insert_with(Vec::new);
+        sources.retain(|s| *s != source);
+        if trusted {
+            sources.push(source);
+        }
+    }
+
+    /// Returns whether the headers of the request may be believed, based on the peer address.
+    fn is_trusted(&self, req: &ServiceRequest) -> bool {
+        if self.trusted_proxies.is_empty() {
+            return true;
+        }
+        match req.peer_addr() {
+            Some(addr) => self
+                .trusted_proxies
+                .iter()
+                .any(|net| net.contains(&addr.ip())),
+            None => false,
+        }
+    }
+
+    /// Returns the address of the client. Headers such as `X-Forwarded-For` are only believed
+    /// when the peer is one of the trusted proxies, since any client could send them, and are
+    /// walked from the right past the trusted proxies, since a client can put any address in
+    /// front of the ones the proxies append. An address which cannot be parsed, such as an
+    /// obfuscated `Forwarded` identifier, hides the client, so `None` is returned.
+    pub(crate) fn client_ip(&self, req: &ServiceRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        if self.trusted_proxies.is_empty() || !self.is_trusted(req) {
+            return peer;
+        }
+        // Like `ConnectionInfo`, prefer `Forwarded` over `X-Forwarded-For`.
+        let forwarded: Vec<String> = forwarded_elements(req)
+            .into_iter()
+            .filter_map(|element| element.client)
+            .collect();
+        let chain = match forwarded.is_empty() {
+            true => header_list(req, &X_FORWARDED_FOR),
+            false => forwarded.iter().map(String::as_str).collect(),
+        };
+        let mut client = peer;
+        for addr in chain.into_iter().rev() {
+            client = parse_ip(addr);
+            match client {
+                Some(ip) if self.trusted_proxies.iter().any(|net| net.contains(&ip)) => {}
+                _ => break,
+            }
+        }
+        client
+    }
+
+    /// Returns whether the client originally made the request over `https`.
+    pub(crate) fn is_secure(&self, req: &ServiceRequest) -> bool {
+        if !self.is_trusted(req) {
+            return socket_is_secure(req);
+        }
+        match &self.sources {
+            None => req.connection_info().scheme() == "https",
+            Some(sources) => sources
+                .iter()
+                .find_map(|source| self.source_is_secure(*source, req))
+                .unwrap_or_else(|| socket_is_secure(req)),
+        }
+    }
+
+    /// Returns what decided the scheme of the request: the name of the trusted source,
+    /// `connection_info` if no sources are configured, or `socket` if no source applied or the
+    /// peer is not trusted.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn scheme_source(&self, req: &ServiceRequest) -> &'static str {
+        if !self.is_trusted(req) {
+            return "socket";
+        }
+        match &self.sources {
+            None => "connection_info",
+            Some(sources) => sources
+                .iter()
+                .find(|source| self.source_is_secure(**source, req).is_some())
+                .map_or("socket", |source| source.name()),
+        }
+    }
+
+    /// Returns the request headers which may change the scheme, host or path of the redirect,
+    /// for the `Vary` header.
+    pub(crate) fn vary_headers(&self) -> Vec<HeaderName> {
+        let mut headers = Vec::new();
+        let x_forwarded_host = match &self.sources {
+            None => {
+                headers.push(header::FORWARDED);
+                headers.push(X_FORWARDED_PROTO);
+                self.x_forwarded_host != Some(false)
+            }
+            Some(sources) => {
+                for source in sources {
+                    headers.extend(match source {
+                        SchemeSource::XForwardedProto => Some(X_FORWARDED_PROTO),
+                        SchemeSource::Forwarded => Some(header::FORWARDED),
+                        SchemeSource::CfVisitor => Some(CF_VISITOR),
+                        SchemeSource::XArrSsl => Some(X_ARR_SSL),
+                        SchemeSource::FrontEndHttps => Some(FRONT_END_HTTPS),
+                        SchemeSource::AppConfig => None,
+                    });
+                }
+                self.x_forwarded_host == Some(true)
+            }
+        };
+        if x_forwarded_host {
+            headers.push(X_FORWARDED_HOST);
+        }
+        if self.x_forwarded_port {
+            headers.push(X_FORWARDED_PORT);
+        }
+        if self.x_forwarded_prefix {
+            headers.push(X_FORWARDED_PREFIX);
+        }
+        if self.hops.is_none() && !self.trusted_proxies.is_empty() {
+            headers.push(X_FORWARDED_FOR);
+        }
+        headers
+    }
+
+    /// Returns the path prefix the application is mounted under according to a trusted
+    /// `X-Forwarded-Prefix` header.
+    pub(crate) fn prefix<'a>(&self, req: &'a ServiceRequest) -> Option<&'a str> {
+        if !self.x_forwarded_prefix || !self.is_trusted(req) {
+            return None;
+        }
+        self.select_hop(req, header_list(req, &X_FORWARDED_PREFIX))
+    }
+
+    /// Returns the host the client originally requested, or `None` if the request does not say.
+    pub(crate) fn host(&self, req: &ServiceRequest) -> Option<String> {
+        if !self.is_trusted(req) {
+            return socket_host(req);
+        }
+        let host = self.forwarded_host(req)?;
+        if self.x_forwarded_port {
+            let port = self.select_hop(req, header_list(req, &X_FORWARDED_PORT));
+            if let Some(port) = port.filter(|port| port.parse::<u16>().is_ok()) {
+                let (name, _) = split_port(&host);
+                return Some(match port {
+                    "80" | "443" => join_port(name, None),
+                    port => join_port(name, Some(port)),
+                });
+            }
+        }
+        Some(host)
+    }
+
+    /// Returns the host according to the trusted sources, ignoring `X-Forwarded-Port`.
+    fn forwarded_host(&self, req: &ServiceRequest) -> Option<String> {
+        // Without any explicit configuration, match actix's `ConnectionInfo`, which trusts
+        // `Forwarded` before `X-Forwarded-Host`.
+        let (sources, x_forwarded_host) = match (&self.sources, self.x_forwarded_host) {
+            (None, None) => (&[SchemeSource::Forwarded][..], true),
+            (None, Some(trust)) => (&[SchemeSource::Forwarded][..], trust),
+            (Some(sources), trust) => (&sources[..], trust.unwrap_or(false)),
+        };
+        let prefer_x_forwarded_host = self.x_forwarded_host == Some(true);
+        let x_forwarded_host = || {
+            let host = self.select_hop(req, header_list(req, &X_FORWARDED_HOST));
+            host.filter(|_| x_forwarded_host).map(str::to_owned)
+        };
+        let forwarded = || {
+            if sources.contains(&SchemeSource::Forwarded) {
+                let element = self.select_hop(req, forwarded_elements(req));
+                element.and_then(|element| element.host)
+            } else {
+                None
+            }
+        };
+        let host = if prefer_x_forwarded_host {
+            x_forwarded_host().or_else(forwarded)
+        } else {
+            forwarded().or_else(x_forwarded_host)
+        };
+        host.or_else(|| socket_host(req))
+    }
+
+    /// Returns whether the request was secure according to the given source, or `None` if the
+    /// source has nothing to say about the request.
+    fn source_is_secure(&self, source: SchemeSource, req: &ServiceRequest) -> Option<bool> {
+        match source {
+            SchemeSource::XForwardedProto => {
+                let protos = header_list(req, &X_FORWARDED_PROTO);
+                Some(self.select_hop(req, protos)?.eq_ignore_ascii_case("https"))
+            }
+            SchemeSource::Forwarded => {
+                let proto = self.select_hop(req, forwarded_elements(req))?.proto?;
+                Some(proto.eq_ignore_ascii_case("https"))
+            }
+            SchemeSource::CfVisitor => {
+                let visitor = req.headers().get(CF_VISITOR)?.to_str().ok()?;
+                Some(cf_visitor_scheme(visitor)?.eq_ignore_ascii_case("https"))
+            }
+            SchemeSource::XArrSsl => req.headers().get(X_ARR_SSL).map(|_| true),
+            SchemeSource::FrontEndHttps => {
+                let value = req.headers().get(FRONT_END_HTTPS)?.to_str().ok()?;
+                Some(value.trim().eq_ignore_ascii_case("on"))
+            }
+            SchemeSource::AppConfig => Some(req.app_config().secure()),
+        }
+    }
+
+    /// Picks the value added by the outermost trusted proxy from a chain of forwarded values,
+    /// which are ordered from the client to the proxy closest to us.
+    fn select_hop<T>(&self, req: &ServiceRequest, mut chain: Vec<T>) -> Option<T> {
+        let index = match self.hops.or_else(|| self.trusted_hops(req)) {
+            Some(hops) => chain.len().saturating_sub(hops.max(1)),
+            None => 0,
+        };
+        if index < chain.len() {
+            Some(chain.swap_remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Counts the trusted proxies the request passed through, by walking `X-Forwarded-For` from
+    /// the right until an address outside of the trusted networks is found. The peer itself
+    /// counts as the first hop. Returns `None` if no trusted networks are configured.
+    fn trusted_hops(&self, req: &ServiceRequest) -> Option<usize> {
+        if self.trusted_proxies.is_empty() {
+            return None;
+        }
+        let trusted = header_list(req, &X_FORWARDED_FOR)
+            .into_iter()
+            .rev()
+            .take_while(|addr| match parse_ip(addr) {
+                Some(ip) => self.trusted_proxies.iter().any(|net| net.contains(&ip)),
+                None => false,
+            })
+            .count();
+        Some(trusted + 1)
+    }
+}
+
+/// Parses an address from a forwarded header, which may include a port and brackets.
+fn parse_ip(addr: &str) -> Option<IpAddr> {
+    addr.parse()
+        .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| addr.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
+
+/// Reads a header value as UTF-8, so internationalized host names can be normalized.
+fn header_str(value: &HeaderValue) -> Option<&str> {
+    std::str::from_utf8(value.as_bytes()).ok()
+}
+
+/// Collects the comma separated values of every instance of a header, in order.
+fn header_list<'a>(req: &'a ServiceRequest, name: &HeaderName) -> Vec<&'a str> {
+    req.headers()
+        .get_all(name)
+        .filter_map(header_str)
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Extracts the scheme from a `CF-Visitor` header value such as `{"scheme":"https"}`.
+///
+/// Cloudflare only ever sends this single key, so rather than pulling in a JSON parser we look
+/// for the `"scheme"` key and read the string following it.
+fn cf_visitor_scheme(value: &str) -> Option<&str> {
+    let rest = value.split_once("\"scheme\"")?.1.trim_start();
+    let rest = rest.strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    Some(&rest[..rest.find('"')?])
+}
+
+/// A single element of a `Forwarded` header, describing one proxy hop.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ForwardedElement {
+    /// The `for` parameter, the address of the client of this hop.
+    client: Option<String>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// Parses every `Forwarded` header of the request into its elements, client first.
+fn forwarded_elements(req: &ServiceRequest) -> Vec<ForwardedElement> {
+    req.headers()
+        .get_all(header::FORWARDED)
+        .filter_map(header_str)
+        .flat_map(parse_forwarded)
+        .collect()
+}
+
+/// Returns whether the `Forwarded` or `X-Forwarded-Proto` headers, in that order as
+/// `ConnectionInfo` reads them, say the request was made over `https`.
+pub(crate) fn forwarded_is_secure(head: &RequestHead) -> bool {
+    let forwarded = head
+        .headers
+        .get_all(header::FORWARDED)
+        .filter_map(header_str)
+        .flat_map(parse_forwarded)
+        .find_map(|element| element.proto);
+    let proto = forwarded.or_else(|| {
+        let value = head.headers.get(&X_FORWARDED_PROTO).and_then(header_str)?;
+        value.split(',').next().map(|proto| proto.trim().to_owned())
+    });
+    proto.is_some_and(|proto| proto.eq_ignore_ascii_case("https"))
+}
+
+/// Parses a `Forwarded` header value such as `for=1.2.3.4;proto=https, for="[::1]";host=a.com`.
+///
+/// Elements are separated by `,` and pairs within an element by `;`. Values may be tokens or
+/// quoted strings, which can contain either separator as well as backslash escapes.
+fn parse_forwarded(value: &str) -> Vec<ForwardedElement> {
+    let mut elements = Vec::new();
+    let mut element = ForwardedElement::default();
+    let mut chars = value.chars().peekable();
+    loop {
+        // A name without `=` has no value, and must not swallow the pairs after it.
+        let mut name = String::new();
+        let mut value = None;
+        let mut separator = None;
+        for c in chars.by_ref() {
+            match c {
+                '=' => {
+                    value = Some(String::new());
+                    break;
+                }
+                ',' | ';' => {
+                    separator = Some(c);
+                    break;
+                }
+                c => name.push(c),
+            }
+        }
+        if let Some(value) = value.as_mut() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        c => value.push(c),
+                    }
+                }
+            }
+            for c in chars.by_ref() {
+                if c == ',' || c == ';' {
+                    separator = Some(c);
+                    break;
+                }
+                value.push(c);
+            }
+        }
+        if let Some(value) = value {
+            let value = value.trim().to_owned();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "for" => element.client = Some(value),
+                "proto" => element.proto = Some(value),
+                "host" => element.host = Some(value),
+                _ => {}
+            }
+        }
+        match separator {
+            Some(';') => {}
+            Some(_) => elements.push(std::mem::take(&mut element)),
+            None => {
+                elements.push(element);
+                return elements;
+            }
+        }
+    }
+}
+
+/// Returns the host the request was made to without consulting any proxy headers.
+fn socket_host(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(header::HOST)
+        .and_then(header_str)
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+        .map(str::to_owned)
+}
+
+/// Returns whether the request arrived over `https` without consulting any headers. The scheme
+/// of an absolute-form request target such as `GET https://example.com/` is chosen by the client,
+/// so only the listener counts.
+fn socket_is_secure(req: &ServiceRequest) -> bool {
+    req.app_config().secure()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    /// Trusts `X-Forwarded-Proto` from peers in `10.0.0.0/8`.
+    fn behind_proxies() -> Forwarding {
+        let mut forwarding = Forwarding {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Forwarding::default()
+        };
+        forwarding.set_sources(&[SchemeSource::XForwardedProto]);
+        forwarding
+    }
+
+    fn request(peer: &str, x_forwarded_for: &str, x_forwarded_proto: &str) -> ServiceRequest {
+        TestRequest::default()
+            .peer_addr(peer.parse().unwrap())
+            .insert_header((X_FORWARDED_FOR, x_forwarded_for))
+            .insert_header((X_FORWARDED_PROTO, x_forwarded_proto))
+            .to_srv_request()
+    }
+
+    #[test]
+    fn walks_trusted_proxies_from_the_right() {
+        let forwarding = behind_proxies();
+        let req = request("10.0.0.2:1234", "198.51.100.7, 10.0.0.1", "https, http");
+        assert_eq!(forwarding.trusted_hops(&req), Some(2));
+        assert_eq!(
+            forwarding.select_hop(&req, header_list(&req, &X_FORWARDED_PROTO)),
+            Some("https")
+        );
+        assert!(forwarding.is_secure(&req));
+    }
+
+    #[test]
+    fn stops_at_the_first_untrusted_address() {
+        let forwarding = behind_proxies();
+        let req = request("10.0.0.2:1234", "10.0.0.9, 198.51.100.7, 10.0.0.1", "https");
+        assert_eq!(forwarding.trusted_hops(&req), Some(2));
+    }
+
+    #[test]
+    fn ignores_a_spoofed_client_most_value() {
+        let forwarding = behind_proxies();
+        // The client sent `X-Forwarded-Proto: https` itself, and the proxies appended theirs.
+        let req = request(
+            "10.0.0.2:1234",
+            "198.51.100.7, 10.0.0.1",
+            "https, http, http",
+        );
+        assert_eq!(
+            forwarding.select_hop(&req, header_list(&req, &X_FORWARDED_PROTO)),
+            Some("http")
+        );
+        assert!(!forwarding.is_secure(&req));
+    }
+
+    #[test]
+    fn uses_the_client_most_value_of_an_entirely_trusted_chain() {
+        let forwarding = behind_proxies();
+        let req = request("10.0.0.2:1234", "10.0.0.3, 10.0.0.1", "https, http");
+        assert_eq!(forwarding.trusted_hops(&req), Some(3));
+        assert_eq!(
+            forwarding.select_hop(&req, header_list(&req, &X_FORWARDED_PROTO)),
+            Some("https")
+        );
+    }
+
+    #[test]
+    fn splits_chained_values_across_headers() {
+        let req = TestRequest::default()
+            .append_header((X_FORWARDED_PROTO, " https ,http,"))
+            .append_header((X_FORWARDED_PROTO, "HTTP"))
+            .to_srv_request();
+        assert_eq!(
+            header_list(&req, &X_FORWARDED_PROTO),
+            vec!["https", "http", "HTTP"]
+        );
+        let mut forwarding = behind_proxies();
+        forwarding.trusted_proxies.clear();
+        forwarding.hops = Some(3);
+        assert!(forwarding.is_secure(&req));
+        forwarding.hops = Some(1);
+        assert!(!forwarding.is_secure(&req));
+    }
+
+    #[test]
+    fn fixed_hops_take_precedence() {
+        let mut forwarding = behind_proxies();
+        forwarding.hops = Some(1);
+        let req = request("10.0.0.2:1234", "198.51.100.7, 10.0.0.1", "https, http");
+        assert_eq!(
+            forwarding.select_hop(&req, header_list(&req, &X_FORWARDED_PROTO)),
+            Some("http")
+        );
+    }
+
+    #[test]
+    fn untrusted_peers_are_judged_by_the_connection() {
+        let forwarding = behind_proxies();
+        let req = request("198.51.100.7:1234", "10.0.0.1", "https");
+        assert!(!forwarding.is_secure(&req));
+        let forwarding = Forwarding::default();
+        assert_eq!(forwarding.trusted_hops(&req), None);
+    }
+
+    fn client_ip(forwarding: &Forwarding, req: &ServiceRequest) -> Option<String> {
+        forwarding.client_ip(req).map(|ip| ip.to_string())
+    }
+
+    #[test]
+    fn client_is_the_first_untrusted_address_from_the_right() {
+        let forwarding = behind_proxies();
+        let req = request("10.0.0.2:1234", "127.0.0.1, 203.0.113.1, 10.0.0.1", "http");
+        assert_eq!(client_ip(&forwarding, &req).as_deref(), Some("203.0.113.1"));
+        let req = request("10.0.0.2:1234", "10.0.0.3, 10.0.0.1", "http");
+        assert_eq!(client_ip(&forwarding, &req).as_deref(), Some("10.0.0.3"));
+        let req = request("10.0.0.2:1234", "127.0.0.1, unknown, 10.0.0.1", "http");
+        assert_eq!(client_ip(&forwarding, &req), None);
+        let req = request("203.0.113.9:1234", "127.0.0.1", "http");
+        assert_eq!(client_ip(&forwarding, &req).as_deref(), Some("203.0.113.9"));
+    }
+
+    #[test]
+    fn client_prefers_forwarded_and_falls_back_to_the_peer() {
+        let forwarding = behind_proxies();
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:1234".parse().unwrap())
+            .insert_header((
+                header::FORWARDED,
+                r#"for=127.0.0.1, for="[2001:db8::1]:4711""#,
+            ))
+            .insert_header((X_FORWARDED_FOR, "198.51.100.7"))
+            .to_srv_request();
+        assert_eq!(client_ip(&forwarding, &req).as_deref(), Some("2001:db8::1"));
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.2:1234".parse().unwrap())
+            .to_srv_request();
+        assert_eq!(client_ip(&forwarding, &req).as_deref(), Some("10.0.0.2"));
+    }
+
+    fn element(proto: Option<&str>, host: Option<&str>) -> ForwardedElement {
+        ForwardedElement {
+            client: None,
+            proto: proto.map(str::to_owned),
+            host: host.map(str::to_owned),
+        }
+    }
+
+    fn with_client(element: ForwardedElement, client: &str) -> ForwardedElement {
+        ForwardedElement {
+            client: Some(client.to_owned()),
+            ..element
+        }
+    }
+
+    #[test]
+    fn parses_pairs_case_insensitively() {
+        assert_eq!(
+            parse_forwarded("for=192.0.2.1;Proto=https;HOST=example.com"),
+            vec![with_client(
+                element(Some("https"), Some("example.com")),
+                "192.0.2.1"
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_multiple_elements_in_order() {
+        assert_eq!(
+            parse_forwarded("proto=http;host=a.com, proto=https;host=b.com,for=10.0.0.1"),
+            vec![
+                element(Some("http"), Some("a.com")),
+                element(Some("https"), Some("b.com")),
+                with_client(element(None, None), "10.0.0.1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values_with_separators_and_escapes() {
+        assert_eq!(
+            parse_forwarded(r#"for="[2001:db8::1]:4711";host="a.com;b,c";proto="ht\"tps""#),
+            vec![with_client(
+                element(Some("ht\"tps"), Some("a.com;b,c")),
+                "[2001:db8::1]:4711"
+            )]
+        );
+        assert_eq!(
+            parse_forwarded(r#"host = "example.com:8080" ; proto = https"#),
+            vec![element(Some("https"), Some("example.com:8080"))]
+        );
+    }
+
+    #[test]
+    fn tolerates_malformed_input() {
+        assert_eq!(parse_forwarded(""), vec![element(None, None)]);
+        let empty = || element(None, None);
+        assert_eq!(parse_forwarded(";;,,"), vec![empty(), empty(), empty()]);
+        assert_eq!(parse_forwarded("proto"), vec![empty()]);
+        assert_eq!(
+            parse_forwarded("proto;host=example.com"),
+            vec![element(None, Some("example.com"))]
+        );
+        assert_eq!(
+            parse_forwarded(r#"host="unterminated"#),
+            vec![element(None, Some("unterminated"))]
+        );
+        assert_eq!(
+            parse_forwarded("proto=https;=oops;host"),
+            vec![element(Some("https"), None)]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Route guards which restrict routes by the scheme of the request.
+
+use crate::forwarded::forwarded_is_secure;
+use crate::skip::Handled;
+use actix_web::{
+    guard::{Guard, GuardContext},
+    http::uri::Scheme,
+};
+
+/// A guard which only matches requests made over `https`, so individual routes can be
+/// restricted to it and fall through to other routes, or `404 Not Found`, otherwise.
+///
+/// Behind [`RedirectHTTPS`](crate::RedirectHTTPS), the guard uses the scheme the middleware found,
+/// so it agrees with the middleware, including its trusted proxies, even while redirection is
+/// turned off. Without the middleware, the guard goes by the scheme of the request target, which
+/// HTTP/2 clients always send but HTTP/1.1 clients only send to proxies, so HTTP/1.1 requests
+/// over TLS do not match. Clients on plain `http` may also send an `https` target, so wrap the
+/// application in the middleware, or use the [`RequireHttps`](crate::RequireHttps) extractor,
+/// which can see the connection, when the guard protects anything sensitive.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{web, App, HttpResponse};
+/// use actix_web_middleware_redirect_https::{guard::HttpsOnly, RedirectHTTPS};
+///
+/// App::new().wrap(RedirectHTTPS::default()).route(
+///     "/account",
+///     web::get()
+///         .guard(HttpsOnly::new())
+///         .to(|| async { HttpResponse::Ok().finish() }),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpsOnly {
+    trust_forwarded: bool,
+}
+
+impl HttpsOnly {
+    /// Creates a guard which matches requests the middleware found to be made over `https`, or
+    /// without the middleware, requests with an `https` target.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a guard which, without the middleware, also believes the `Forwarded` and
+    /// `X-Forwarded-Proto` headers. Any client can send them, so only use this when every
+    /// request passes through a proxy which overwrites them.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{web, App, HttpResponse};
+    /// use actix_web_middleware_redirect_https::guard::HttpsOnly;
+    ///
+    /// App::new().route(
+    ///     "/account",
+    ///     web::get()
+    ///         .guard(HttpsOnly::trust_forwarded())
+    ///         .to(|| async { HttpResponse::Ok().finish() }),
+    /// );
+    /// ```
+    pub fn trust_forwarded() -> Self {
+        HttpsOnly {
+            trust_forwarded: true,
+        }
+    }
+}
+
+impl Guard for HttpsOnly {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        if let Some(handled) = ctx.req_data().get::<Handled>() {
+            return handled.secure;
+        }
+        let head = ctx.head();
+        head.uri.scheme() == Some(&Scheme::HTTPS)
+            || (self.trust_forwarded && forwarded_is_secure(head))
+    }
+}
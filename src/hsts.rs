@@ -0,0 +1,377 @@
+use crate::exclude::matches_prefix;
+use crate::forwarded::Forwarding;
+use crate::ConfigError;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    Error,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use ipnet::IpNet;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+/// The minimum `max-age` accepted by the HSTS preload list.
+const PRELOAD_MIN_MAX_AGE: Duration = Duration::from_secs(31_536_000);
+
+/// A `Strict-Transport-Security` policy, for use with [`RedirectHTTPS::hsts_policy`].
+///
+/// It is also a middleware on its own, for applications behind a proxy which terminates TLS
+/// and redirects to `https` itself. It adds the header to responses for requests which arrived
+/// over `https` according to actix's `ConnectionInfo`, unless the application already set one.
+/// A policy which fails [`validate`](Self::validate) is still used, and logged as a warning when
+/// the middleware starts with the `logging` feature; [`build`](Self::build) reports it up front.
+///
+/// Which requests arrived over `https` is decided from headers such as `X-Forwarded-Proto`,
+/// which any client can send, unless the middleware is limited to
+/// [`trusted_proxies`](Self::trusted_proxies).
+///
+/// [`RedirectHTTPS::hsts_policy`]: crate::RedirectHTTPS::hsts_policy
+///
+/// ## Usage
+/// ```
+/// use std::time::Duration;
+/// use actix_web::{App, web, HttpResponse};
+/// use actix_web_middleware_redirect_https::Hsts;
+///
+/// let hsts = Hsts::new(Duration::from_secs(63072000))
+///     .include_subdomains(true)
+///     .preload(true);
+///
+/// App::new()
+///     .wrap(hsts.build().unwrap())
+///     .route("/", web::get().to(|| async { HttpResponse::Ok().finish() }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hsts {
+    max_age: Duration,
+    include_subdomains: bool,
+    preload: bool,
+    ramp_up: Option<(SystemTime, Vec<(Duration, Duration)>)>,
+    /// Policies for paths, or `None` to send no header, by path prefix.
+    paths: Vec<(String, Option<Hsts>)>,
+    /// The proxies whose headers are believed when used as a middleware on its own.
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl Hsts {
+    /// Creates a policy with the given `max-age` and no other directives.
+    pub fn new(max_age: Duration) -> Self {
+        Hsts {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+            ramp_up: None,
+            paths: Vec::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    /// Adds the `includeSubDomains` directive, so the policy also applies to all subdomains.
+    pub fn include_subdomains(mut self, include_subdomains: bool) -> Self {
+        self.include_subdomains = include_subdomains;
+        self
+    }
+
+    /// Adds the `preload` directive, signalling consent to be included in browser preload lists.
+    pub fn preload(mut self, preload: bool) -> Self {
+        self.preload = preload;
+        self
+    }
+
+    /// Ramps the `max-age` up over time, starting at `start`. Each stage is a `(max_age, period)`
+    /// pair: its `max_age` is emitted for `period` before moving on to the next stage, and once
+    /// all stages have elapsed the `max-age` given to [`new`](Self::new) is used. Since browsers
+    /// remember the policy for `max-age`, starting small limits the damage if `https` breaks.
+    ///
+    /// `start` should be a fixed point in time, such as the date of the deployment, rather than
+    /// [`SystemTime::now`], so that restarts do not reset the schedule.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use actix_web_middleware_redirect_https::Hsts;
+    ///
+    /// const DAY: u64 = 24 * 60 * 60;
+    /// let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    ///
+    /// // 5 minutes for the first week, then 30 days for a month, then 1 year.
+    /// let hsts = Hsts::new(Duration::from_secs(365 * DAY)).ramp_up(
+    ///     start,
+    ///     &[
+    ///         (Duration::from_secs(300), Duration::from_secs(7 * DAY)),
+    ///         (Duration::from_secs(30 * DAY), Duration::from_secs(30 * DAY)),
+    ///     ],
+    /// );
+    /// ```
+    pub fn ramp_up(mut self, start: SystemTime, stages: &[(Duration, Duration)]) -> Self {
+        self.ramp_up = Some((start, stages.to_vec()));
+        self
+    }
+
+    /// Uses another policy for the given path and anything below it, or sends no header at all
+    /// if `policy` is `None`. Paths match like
+    /// [`exclude_paths`](crate::RedirectHTTPS::exclude_paths), and the longest matching path
+    /// wins.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web_middleware_redirect_https::Hsts;
+    ///
+    /// let hsts = Hsts::new(Duration::from_secs(63072000))
+    ///     .path("/beta", Some(Hsts::new(Duration::from_secs(300))))
+    ///     .path("/legacy", None);
+    /// ```
+    pub fn path(mut self, prefix: &str, policy: Option<Hsts>) -> Self {
+        self.paths.retain(|(path, _)| path != prefix);
+        self.paths.push((prefix.to_owned(), policy));
+        self
+    }
+
+    /// Only believes headers such as `X-Forwarded-Proto` from the given proxies when used as a
+    /// middleware on its own. Requests from any other peer are only `https` if they arrived
+    /// over TLS. Like [`RedirectHTTPS::trusted_proxies`], an empty list believes every peer,
+    /// so that any client can claim its plain `http` request was made over `https`.
+    ///
+    /// This has no effect on a policy given to [`RedirectHTTPS::hsts_policy`], which uses the
+    /// trusted proxies of the [`RedirectHTTPS`] middleware.
+    ///
+    /// [`RedirectHTTPS`]: crate::RedirectHTTPS
+    /// [`RedirectHTTPS::trusted_proxies`]: crate::RedirectHTTPS::trusted_proxies
+    /// [`RedirectHTTPS::hsts_policy`]: crate::RedirectHTTPS::hsts_policy
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::Hsts;
+    ///
+    /// App::new().wrap(
+    ///     Hsts::new(Duration::from_secs(63072000)).trusted_proxies(&["10.0.0.0/8".parse().unwrap()]),
+    /// );
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: &[IpNet]) -> Self {
+        self.trusted_proxies = proxies.to_vec();
+        self
+    }
+
+    /// Returns the `max-age` which applies at the given point in time.
+    fn max_age_at(&self, now: SystemTime) -> Duration {
+        if let Some((start, stages)) = &self.ramp_up {
+            // Before the start of the schedule the first stage applies.
+            let mut elapsed = now.duration_since(*start).unwrap_or_default();
+            for &(max_age, period) in stages {
+                if elapsed < period {
+                    return max_age;
+                }
+                elapsed -= period;
+            }
+        }
+        self.max_age
+    }
+
+    /// Checks the policy against the preload list eligibility rules, if `preload` is set: the
+    /// `max-age` must be at least one year, including every stage of a [`ramp_up`](Self::ramp_up),
+    /// and `includeSubDomains` must be present. The policies for [`path`](Self::path)s are
+    /// checked too.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for policy in self.paths.iter().filter_map(|(_, policy)| policy.as_ref()) {
+            policy.validate()?;
+        }
+        if self.preload {
+            let stages = self.ramp_up.iter().flat_map(|(_, stages)| stages);
+            let min_max_age = stages
+                .map(|&(max_age, _)| max_age)
+                .fold(self.max_age, Duration::min);
+            if min_max_age < PRELOAD_MIN_MAX_AGE {
+                return Err(ConfigError::HstsPreloadMaxAge(min_max_age));
+            }
+            if !self.include_subdomains {
+                return Err(ConfigError::HstsPreloadSubdomains);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the policy with [`validate`](Self::validate), returning it if it is valid.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web_middleware_redirect_https::{ConfigError, Hsts};
+    ///
+    /// assert_eq!(
+    ///     Hsts::new(Duration::from_secs(63072000)).preload(true).build().err(),
+    ///     Some(ConfigError::HstsPreloadSubdomains)
+    /// );
+    /// ```
+    pub fn build(self) -> Result<Self, ConfigError> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Returns the header for a request to `path`, if the policy for that path sends one.
+    pub(crate) fn header_for(&self, path: &str) -> Option<HeaderValue> {
+        let policy = self
+            .paths
+            .iter()
+            .filter(|(prefix, _)| matches_prefix(prefix, path))
+            .max_by_key(|(prefix, _)| prefix.len());
+        match policy {
+            Some((_, policy)) => policy.as_ref().map(Hsts::header_value),
+            None => Some(self.header_value()),
+        }
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let max_age = self.max_age_at(SystemTime::now());
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        HeaderValue::from_str(&value).unwrap()
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Hsts
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = HstsService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        #[cfg(feature = "logging")]
+        if let Err(error) = self.validate() {
+            crate::logging::invalid_config(&error);
+        }
+        let mut forwarding = Forwarding::default();
+        forwarding.trusted_proxies = self.trusted_proxies.clone();
+        ok(HstsService {
+            service,
+            policy: self.clone(),
+            forwarding,
+        })
+    }
+}
+
+pub struct HstsService<S> {
+    service: S,
+    policy: Hsts,
+    forwarding: Forwarding,
+}
+
+impl<S, B> Service<ServiceRequest> for HstsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let secure = self.forwarding.is_secure(&req);
+        let value = self.policy.header_for(req.path()).filter(|_| secure);
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Some(value) = value {
+                let headers = res.headers_mut();
+                if !headers.contains_key(header::STRICT_TRANSPORT_SECURITY) {
+                    headers.insert(header::STRICT_TRANSPORT_SECURITY, value);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse};
+    use std::net::SocketAddr;
+
+    const YEAR: Duration = PRELOAD_MIN_MAX_AGE;
+
+    #[test]
+    fn validates_path_policies() {
+        let preload = Hsts::new(YEAR).include_subdomains(true).preload(true);
+        assert_eq!(preload.clone().path("/legacy", None).validate(), Ok(()));
+        let short = Hsts::new(Duration::from_secs(300)).preload(true);
+        assert_eq!(
+            preload.clone().path("/beta", Some(short)).validate(),
+            Err(ConfigError::HstsPreloadMaxAge(Duration::from_secs(300)))
+        );
+        let nested = Hsts::new(YEAR).path("/a", Some(Hsts::new(YEAR).preload(true)));
+        assert_eq!(
+            Hsts::new(YEAR).path("/", Some(nested)).validate(),
+            Err(ConfigError::HstsPreloadSubdomains)
+        );
+    }
+
+    #[actix_web::test]
+    async fn invalid_policy_is_still_used() {
+        let hsts = Hsts::new(Duration::from_secs(300)).preload(true);
+        assert!(hsts.clone().build().is_err());
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(hsts)
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("X-Forwarded-Proto", "https"))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers()
+                .get(header::STRICT_TRANSPORT_SECURITY)
+                .unwrap(),
+            "max-age=300; preload"
+        );
+    }
+
+    #[actix_web::test]
+    async fn forwarded_scheme_is_only_believed_from_trusted_proxies() {
+        let proxy: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let client: SocketAddr = "203.0.113.7:443".parse().unwrap();
+        let hsts = Hsts::new(YEAR).trusted_proxies(&["10.0.0.0/8".parse().unwrap()]);
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(hsts)
+                .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+        for (peer, expected) in [(proxy, true), (client, false)] {
+            let req = actix_web::test::TestRequest::default()
+                .peer_addr(peer)
+                .insert_header(("X-Forwarded-Proto", "https"))
+                .to_request();
+            let res = actix_web::test::call_service(&app, req).await;
+            assert_eq!(
+                res.headers()
+                    .contains_key(header::STRICT_TRANSPORT_SECURITY),
+                expected,
+                "{}",
+                peer
+            );
+        }
+    }
+}
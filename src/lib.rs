@@ -4,15 +4,186 @@
 
 use actix_service::{Service, Transform};
 use actix_web::{
+    body::{EitherBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
-    http, Error, HttpResponse,
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        uri::Authority,
+        Method, StatusCode, Uri,
+    },
+    middleware::TrailingSlash,
+    web::Bytes,
+    Error, HttpMessage, HttpResponse, HttpResponseBuilder,
 };
-use futures::future::{ok, Either, Ready};
+use futures::future::{ok, Either, LocalBoxFuture, MapOk, Ready, TryFutureExt};
+use ipnet::IpNet;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "tokio")]
+use tokio::sync::watch;
+
+mod acme;
+mod authority;
+mod body;
+#[cfg(feature = "sentry")]
+mod breadcrumbs;
+mod builder;
+mod cache;
+#[cfg(feature = "serde")]
+mod config;
+mod control;
+#[cfg(feature = "metrics")]
+mod counters;
+mod env;
+mod error;
+mod exclude;
+mod extract;
+mod forwarded;
+pub mod guard;
+mod hsts;
+#[cfg(feature = "logging")]
+mod logging;
+mod nel;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "url")]
+mod parsed;
+mod policy;
+mod preflight;
+mod replace;
+mod request_id;
+mod scheme;
+mod skip;
+mod stats;
+#[cfg(feature = "statsd")]
+mod statsd;
+mod template;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "tokio")]
+mod updates;
+mod upgrade_insecure;
+mod upgrade_signal;
+
+pub use body::RedirectTarget;
+pub use builder::{RedirectHTTPSBuilder, Unvalidated};
+#[cfg(feature = "serde")]
+pub use config::RedirectConfig;
+pub use control::RedirectControl;
+pub use error::ConfigError;
+pub use exclude::HealthCheckPolicy;
+pub use extract::{RequireHttps, RequireHttpsConfig};
+pub use forwarded::SchemeSource;
+pub use hsts::{Hsts, HstsService};
+pub use policy::{AsyncRedirectPolicy, RedirectDecision, RedirectPolicy};
+pub use preflight::PreflightPolicy;
+pub use scheme::{Scheme, WebSocketPolicy};
+pub use skip::SkipHttpsRedirect;
+pub use stats::RedirectStats;
+#[cfg(feature = "statsd")]
+pub use statsd::StatsdClient;
+pub use template::LocationTemplate;
+pub use upgrade_insecure::{UpgradeInsecureRequests, UpgradeInsecureRequestsService};
+pub use upgrade_signal::UpgradeSignalPolicy;
+
+use acme::AcmeResponder;
+#[cfg(not(feature = "url"))]
+use authority::strip_query_params;
+use authority::{
+    constant_time_eq, host_matches, is_path_byte, is_url_byte, join_port, normalize_host,
+    normalize_path, percent_encode, replace_name, split_port, strip_default_port, strip_userinfo,
+    HostRule, PortMapping,
+};
+use body::{accepts_json, json_error, Body, Negotiation};
+use cache::{CacheKey, LocationCache};
+use exclude::{matches_prefix, Exclusions};
+use forwarded::Forwarding;
+use nel::NetworkErrorLogging;
+use preflight::is_preflight;
+use replace::Replacements;
+use scheme::is_websocket;
+use skip::Handled;
+use stats::Event;
+#[cfg(feature = "statsd")]
+use statsd::DecisionTimer;
+use template::LocationParts;
+#[cfg(feature = "tokio")]
+use updates::Updates;
+use upgrade_signal::sends_upgrade_signal;
+
+/// The `User-Agent` prefixes of the health checks of cloud load balancers.
+const CLOUD_HEALTH_CHECKERS: &[&str] = &["GoogleHC/", "ELB-HealthChecker/"];
+
+/// A callback run with the request and the `Location` of every redirect.
+type RedirectHook = Arc<dyn Fn(&ServiceRequest, &str) + Send + Sync>;
+
+/// A predicate deciding whether a request may be redirected, set with
+/// [`RedirectHTTPS::redirect_if`].
+type RedirectPredicate = Arc<dyn Fn(&ServiceRequest) -> bool + Send + Sync>;
+
+/// The name of the `Expect-CT` header.
+const EXPECT_CT: HeaderName = HeaderName::from_static("expect-ct");
+
+/// The default for [`RedirectHTTPS::max_uri_length`].
+const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
 
 /// Middleware for `actix-web` which redirects all `http` requests to `https` with optional url
 /// string replacements.
 ///
+/// Any userinfo in the host, such as the `user:pass@` of an absolute-form request, is dropped
+/// so credentials are never reflected into the redirect.
+///
+/// Characters which are not allowed in a url, such as spaces or line breaks smuggled in via
+/// forwarded headers or replacements, are percent-encoded before the `Location` header is
+/// written. The url is built as an `http::Uri`, and if no valid absolute url can be built, such as
+/// for an invalid host or after replacements broke it, `400 Bad Request` is returned instead.
+/// With the `url` feature, the url is also parsed with the `url` crate before replacements, which
+/// normalizes the host, port and escaping, and query parameters are stripped on the parsed pairs.
+///
+/// With the `tracing` feature, the decision about each request is made in a debug span named
+/// `redirect_https`, which records the source of the scheme, the decision (`pass`, `redirect`,
+/// `respond` or `acme`), the status code and the target `Location`.
+///
+/// With the `metrics` feature, the `https_redirects_total`, `http_passthroughs_total` and
+/// `insecure_post_rejections_total` counters are incremented through the `metrics` facade.
+///
+/// With the `otel` feature, each redirect sets the `http.scheme`, `http.target` and
+/// `redirect.reason` attributes on the active OpenTelemetry span.
+///
+/// With the `sentry` feature, every `http` request which is redirected or rejected adds a
+/// breadcrumb with its host, path and the decision to the current Sentry hub.
+///
+/// With the `logging` feature, each redirect is recorded at the info level with the `log` crate,
+/// under the target `redirect_https`, with the peer address, host, path, status code and location
+/// as key-values. Invalid options and urls which cannot be built are logged as warnings.
+///
+/// When instances of the middleware are nested, such as on an `App` and one of its scopes, each
+/// applies its own configuration to the requests the outer ones passed on, so instances for
+/// different schemes can be stacked and a scope can redirect paths the `App` exempts. Requests
+/// which an instance with the same configuration already handled, such as when one middleware
+/// is wrapped twice, are passed through. Use [`scope`](Self::scope) on the outermost instance to
+/// make part of the application follow other rules altogether.
+///
+/// Generated responses carry a `Vary` header listing the request headers, such as trusted
+/// forwarded headers, which the response depends on.
+///
+/// Cloning is cheap, since the options are shared behind an [`Arc`] and only copied when one
+/// of the clones is changed. Configure the middleware once and clone it into the `HttpServer`
+/// factory to share large replacement tables and exclusion lists between workers.
+///
+/// Setting the environment variable `FORCE_HTTPS` to a false value such as `0` disables the
+/// middleware however it was configured in code, for emergency rollbacks without a deploy. The
+/// variable is read when each worker builds its services, so it takes effect on restart.
+///
 /// ## Usage
 /// ```
 /// use actix_web::{App, web, HttpResponse};
@@ -20,21 +191,210 @@ use std::task::{Context, Poll};
 ///
 /// App::new()
 ///     .wrap(RedirectHTTPS::default())
-///     .route("/", web::get().to(|| HttpResponse::Ok()
-///                                     .content_type("text/plain")
-///                                     .body("Always HTTPS!")));
+///     .route("/", web::get().to(|| async {
+///         HttpResponse::Ok()
+///             .content_type("text/plain")
+///             .body("Always HTTPS!")
+///     }));
 /// ```
-#[derive(Default, Clone)]
-pub struct RedirectHTTPS {
+#[derive(Clone)]
+pub struct RedirectHTTPS<State = ()> {
+    /// The options, shared by every worker's service and copied only when changed.
+    options: Arc<Options>,
+    /// Whether the options are still to be validated, see [`RedirectHTTPSBuilder`].
+    state: PhantomData<State>,
+}
+
+/// The options of [`RedirectHTTPS`], behind an [`Arc`] so the replacement tables, exclusion
+/// lists and compiled matchers are built once and shared rather than cloned into every worker.
+#[derive(Clone)]
+struct Options {
     disabled: bool,
-    replacements: Vec<(String, String)>,
+    control: Option<RedirectControl>,
+    stats: Option<RedirectStats>,
+    #[cfg(feature = "statsd")]
+    statsd: Option<StatsdClient>,
+    #[cfg(feature = "tokio")]
+    updates: Option<Updates>,
+    replacements: Replacements,
+    status_code: StatusCode,
+    preserve_method: bool,
+    reject_unsafe_methods: Option<StatusCode>,
+    upgrade_required: bool,
+    websocket: WebSocketPolicy,
+    preflight: PreflightPolicy,
+    upgrade_signal: UpgradeSignalPolicy,
+    redirect_cache: Option<Duration>,
+    redirect_expires: bool,
+    location_cache: Option<usize>,
+    extra_headers: Vec<(HeaderName, HeaderValue)>,
+    request_id_header: Option<HeaderName>,
+    body: Body,
+    json_body: bool,
+    negotiation: Option<Negotiation>,
+    hsts: Option<Hsts>,
+    network_error_logging: Option<NetworkErrorLogging>,
+    alt_svc: Option<HeaderValue>,
+    expect_ct: Option<HeaderValue>,
+    forwarding: Forwarding,
+    path_prefix: String,
+    exclusions: Exclusions,
+    exempt_peers: Vec<IpNet>,
+    exempt_headers: Vec<(HeaderName, HeaderValue)>,
+    exempt_user_agents: Vec<String>,
+    exempt_cloud_health_checks: bool,
+    exempt_localhost: bool,
+    acme_responder: Option<AcmeResponder>,
+    ports: PortMapping,
+    host_rules: Vec<HostRule>,
+    location_template: Option<LocationTemplate>,
+    preserve_query: bool,
+    stripped_query_params: Vec<String>,
+    marker_query_param: Option<String>,
+    path_rewrites: Vec<(String, String)>,
+    preserve_path: bool,
+    normalize_path: Option<TrailingSlash>,
+    redirect_to: Option<String>,
+    #[cfg(feature = "regex")]
+    regex_replacements: Vec<(Regex, String)>,
+    strip_default_port: bool,
+    from_scheme: Scheme,
+    to_scheme: Scheme,
+    normalize_host: bool,
+    canonical_host: Option<String>,
+    host_rewrites: Vec<(String, String)>,
+    allowed_hosts: Vec<String>,
+    disallowed_host_response: Arc<dyn Fn() -> HttpResponse + Send + Sync>,
+    fallback_host: Option<String>,
+    reject_missing_host: bool,
+    max_uri_length: usize,
+    on_redirect: Option<RedirectHook>,
+    policy: Option<Arc<dyn RedirectPolicy>>,
+    async_policy: Option<Arc<dyn AsyncRedirectPolicy>>,
+    redirect_if: Option<RedirectPredicate>,
+    scopes: Vec<(String, Arc<Options>)>,
+}
+
+/// Middleware for `actix-web` which redirects requests made over one scheme to another, created
+/// with [`RedirectScheme::new`]. [`RedirectHTTPS`] is the preset for `http` to `https`.
+pub type RedirectScheme = RedirectHTTPS;
+
+/// Middleware for `actix-web` which redirects `http` to `https` with recommended security
+/// settings, created with [`SecureRedirect::recommended`].
+pub type SecureRedirect = RedirectHTTPS;
+
+impl Default for RedirectHTTPS {
+    fn default() -> Self {
+        RedirectHTTPS {
+            options: Arc::new(Options::default()),
+            state: PhantomData,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            disabled: false,
+            control: None,
+            stats: None,
+            #[cfg(feature = "statsd")]
+            statsd: None,
+            #[cfg(feature = "tokio")]
+            updates: None,
+            replacements: Replacements::default(),
+            status_code: StatusCode::MOVED_PERMANENTLY,
+            preserve_method: false,
+            reject_unsafe_methods: None,
+            upgrade_required: false,
+            websocket: WebSocketPolicy::Redirect,
+            preflight: PreflightPolicy::Redirect,
+            upgrade_signal: UpgradeSignalPolicy::Redirect,
+            redirect_cache: None,
+            redirect_expires: false,
+            location_cache: None,
+            extra_headers: Vec::new(),
+            request_id_header: None,
+            body: Body::Empty,
+            json_body: false,
+            negotiation: None,
+            hsts: None,
+            network_error_logging: None,
+            alt_svc: None,
+            expect_ct: None,
+            forwarding: Forwarding::default(),
+            path_prefix: String::new(),
+            exclusions: Exclusions::default(),
+            exempt_peers: Vec::new(),
+            exempt_headers: Vec::new(),
+            exempt_user_agents: Vec::new(),
+            exempt_cloud_health_checks: false,
+            exempt_localhost: false,
+            acme_responder: None,
+            ports: PortMapping::default(),
+            host_rules: Vec::new(),
+            location_template: None,
+            preserve_query: true,
+            stripped_query_params: Vec::new(),
+            marker_query_param: None,
+            path_rewrites: Vec::new(),
+            preserve_path: true,
+            normalize_path: None,
+            redirect_to: None,
+            #[cfg(feature = "regex")]
+            regex_replacements: Vec::new(),
+            strip_default_port: true,
+            from_scheme: Scheme::Http,
+            to_scheme: Scheme::Https,
+            normalize_host: false,
+            canonical_host: None,
+            host_rewrites: Vec::new(),
+            allowed_hosts: Vec::new(),
+            disallowed_host_response: Arc::new(|| HttpResponse::BadRequest().finish()),
+            fallback_host: None,
+            reject_missing_host: false,
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+            on_redirect: None,
+            policy: None,
+            async_policy: None,
+            redirect_if: None,
+            scopes: Vec::new(),
+        }
+    }
 }
 
 impl RedirectHTTPS {
+    /// Creates a [`RedirectHTTPSBuilder`], which takes the same options and validates them all
+    /// at once with [`build`](RedirectHTTPSBuilder::build).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::StatusCode};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::builder()
+    ///             .status_code(StatusCode::FOUND)
+    ///             .exclude_paths(&["/healthz"])
+    ///             .build()
+    ///             .unwrap(),
+    ///     );
+    /// ```
+    pub fn builder() -> RedirectHTTPSBuilder {
+        RedirectHTTPS {
+            options: Self::default().options,
+            state: PhantomData,
+        }
+    }
+
     /// Creates a RedirectHTTPS middleware which also performs string replacement on the final url.
     /// This is useful when not running on the default web and ssl ports (80 and 443) since we will
     /// need to change the development web port in the hostname to the development ssl port.
     ///
+    /// The replacements are applied together in a single pass over the url. Where patterns
+    /// overlap the earlier one wins, and replaced text is not matched again by later patterns.
+    ///
     /// ## Usage
     /// ```
     /// use actix_web::{App, web, HttpResponse};
@@ -42,78 +402,2792 @@ impl RedirectHTTPS {
     ///
     /// App::new()
     ///     .wrap(RedirectHTTPS::with_replacements(&[(":8080".to_owned(), ":8443".to_owned())]))
-    ///     .route("/", web::get().to(|| HttpResponse::Ok()
-    ///                                     .content_type("text/plain")
-    ///                                     .body("Always HTTPS on non-default ports!")));
+    ///     .route("/", web::get().to(|| async {
+    ///         HttpResponse::Ok()
+    ///             .content_type("text/plain")
+    ///             .body("Always HTTPS on non-default ports!")
+    ///     }));
     /// ```
     pub fn with_replacements(replacements: &[(String, String)]) -> Self {
+        let options = Options {
+            replacements: Replacements::new(replacements),
+            ..Options::default()
+        };
         RedirectHTTPS {
-            disabled: false,
-            replacements: replacements.to_vec(),
+            options: Arc::new(options),
+            state: PhantomData,
         }
     }
 
+    /// Creates a middleware which redirects requests made over one scheme to another, with all
+    /// the same options as [`RedirectHTTPS`], which is the preset for `http` to `https`. For
+    /// example `https` to `http` for local development behind a reverse proxy, or `ws` to `wss`
+    /// for WebSocket handshakes. See [`schemes`](Self::schemes).
+    ///
+    /// Redirecting from a scheme to itself is reported as [`ConfigError::SameScheme`] when
+    /// the options are validated.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectScheme, Scheme};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectScheme::new(Scheme::Ws, Scheme::Wss));
+    /// ```
+    pub fn new(from: Scheme, to: Scheme) -> Self {
+        Self::default().schemes(from, to)
+    }
+
+    /// Creates a RedirectHTTPS middleware which keeps Kubernetes liveness and readiness probes
+    /// over `http` working, by passing `/healthz`, `/readyz` and `/livez` through to the
+    /// application along with any request whose `User-Agent` starts with `kube-probe/`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::kubernetes());
+    /// ```
+    pub fn kubernetes() -> Self {
+        Self::default()
+            .health_checks(
+                &["/healthz", "/readyz", "/livez"],
+                HealthCheckPolicy::PassThrough,
+            )
+            .exempt_user_agents(&["kube-probe/"])
+    }
+
+    /// Creates a RedirectHTTPS middleware with settings which are secure for most deployments
+    /// behind a reverse proxy, for those who do not want to go through every option:
+    ///
+    /// - Unsafe methods such as `POST` are redirected with `308 Permanent Redirect`, so they are
+    ///   not turned into `GET` requests, see [`preserve_method`](Self::preserve_method).
+    /// - `https` responses get `Strict-Transport-Security` with a `max-age` of one year, see
+    ///   [`hsts`](Self::hsts).
+    /// - Hosts are normalized, and requests without a usable host are rejected rather than
+    ///   redirected to a guessed one, see [`normalize_host`](Self::normalize_host) and
+    ///   [`reject_missing_host`](Self::reject_missing_host).
+    /// - Forwarded headers are only believed from loopback and private network addresses,
+    ///   where reverse proxies usually live, see [`trusted_proxies`](Self::trusted_proxies).
+    /// - The scheme is taken from `X-Forwarded-Proto` alone, and from the value added by the
+    ///   outermost trusted proxy rather than the one the client sent, see
+    ///   [`scheme_sources`](Self::scheme_sources). The host is taken from the `Host` header.
+    ///
+    /// Any of these can be changed afterwards, such as to add a
+    /// [`canonical_host`](Self::canonical_host).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::SecureRedirect;
+    ///
+    /// App::new()
+    ///     .wrap(SecureRedirect::recommended().canonical_host("example.com"));
+    /// ```
+    pub fn recommended() -> Self {
+        let proxies: Vec<IpNet> = [
+            "127.0.0.0/8",
+            "10.0.0.0/8",
+            "172.16.0.0/12",
+            "192.168.0.0/16",
+            "::1/128",
+            "fc00::/7",
+        ]
+        .iter()
+        .map(|net| net.parse().unwrap())
+        .collect();
+        Self::default()
+            .preserve_method(true)
+            .hsts(Duration::from_secs(365 * 24 * 60 * 60))
+            .normalize_host(true)
+            .reject_missing_host(true)
+            .trusted_proxies(&proxies)
+            .scheme_sources(&[SchemeSource::XForwardedProto])
+    }
+
+    /// Creates a RedirectHTTPS middleware configured from environment variables, so 12-factor
+    /// deployments can configure it without code changes. Unset variables keep their defaults.
+    ///
+    /// - `REDIRECT_HTTPS_ENABLED`: `true` or `false`, see [`set_enabled`](Self::set_enabled).
+    /// - `REDIRECT_HTTPS_PORT`: the `https` port, see [`to_port`](Self::to_port).
+    /// - `REDIRECT_HTTPS_REPLACEMENTS`: comma separated replacements such as `:8080->:8443`, see
+    ///   [`with_replacements`](Self::with_replacements).
+    /// - `REDIRECT_HTTPS_STATUS`: the redirect status code, see
+    ///   [`status_code`](Self::status_code).
+    /// - `REDIRECT_HTTPS_EXCLUDE`: comma separated path prefixes, see
+    ///   [`exclude_paths`](Self::exclude_paths).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::from_env().expect("invalid redirect configuration"));
+    /// ```
+    pub fn from_env() -> Result<Self, ConfigError> {
+        env::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Creates a RedirectHTTPS middleware from a deserialized [`RedirectConfig`], checking the
+    /// options as [`build`](RedirectHTTPSBuilder::build) does.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectConfig, RedirectHTTPS};
+    ///
+    /// let config: RedirectConfig = serde_json::from_str(r#"{"port": 8443}"#).unwrap();
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::from_config(config).unwrap());
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn from_config(config: RedirectConfig) -> Result<Self, ConfigError> {
+        config.build()
+    }
+
+    /// Creates a RedirectHTTPS middleware which follows the configurations sent on a `watch`
+    /// channel, so the replacements, exclusions, status code and every other option can be
+    /// swapped at runtime. Each worker picks up the latest configuration before handling its
+    /// next request.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::StatusCode};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use tokio::sync::watch;
+    ///
+    /// let (updates, receiver) = watch::channel(RedirectHTTPS::default());
+    /// let app = App::new()
+    ///     .wrap(RedirectHTTPS::from_watch(receiver));
+    /// updates.send(RedirectHTTPS::default().status_code(StatusCode::PERMANENT_REDIRECT)).unwrap();
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn from_watch(mut updates: watch::Receiver<RedirectHTTPS>) -> Self {
+        let mut config = updates.borrow_and_update().clone();
+        config.options_mut().updates = Some(Updates::Middleware(updates));
+        config
+    }
+
+    /// Like [`from_watch`](Self::from_watch), but follows the [`RedirectConfig`]s sent on the
+    /// channel, such as by a configuration service, so the middleware does not have to be rebuilt
+    /// for every change. Returns an error if the current configuration is invalid. Invalid
+    /// configurations sent later are skipped, keeping the previous one, and logged with the
+    /// `logging` feature.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectConfig, RedirectHTTPS};
+    /// use tokio::sync::watch;
+    ///
+    /// let (updates, receiver) = watch::channel(RedirectConfig::default());
+    /// let app = App::new()
+    ///     .wrap(RedirectHTTPS::from_config_watch(receiver).unwrap());
+    /// let config: RedirectConfig = serde_json::from_str(r#"{"status_code": 308}"#).unwrap();
+    /// updates.send(config).unwrap();
+    /// ```
+    #[cfg(all(feature = "tokio", feature = "serde"))]
+    pub fn from_config_watch(
+        mut updates: watch::Receiver<RedirectConfig>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = updates.borrow_and_update().clone().build()?;
+        config.options_mut().updates = Some(Updates::Config(updates));
+        Ok(config)
+    }
+}
+
+impl<State> RedirectHTTPS<State> {
     pub fn set_enabled(mut self, enabled: bool) -> Self {
-        self.disabled = !enabled;
+        self.options_mut().disabled = !enabled;
         self
     }
-}
 
-impl<S> Transform<S, ServiceRequest> for RedirectHTTPS
-where
-    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
-    S::Future: 'static,
-{
-    type Response = ServiceResponse;
-    type Error = Error;
-    type InitError = ();
-    type Transform = RedirectHTTPSService<S>;
-    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+    /// Disables the middleware in builds with `debug_assertions`, such as a local `cargo run`, so
+    /// plain `http` isn't redirected to an `https` port that doesn't exist. Release builds are
+    /// unaffected.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().disable_in_debug());
+    /// ```
+    pub fn disable_in_debug(mut self) -> Self {
+        if cfg!(debug_assertions) {
+            self.options_mut().disabled = true;
+        }
+        self
+    }
 
-    fn new_transform(&self, service: S) -> Self::Future {
-        ok(RedirectHTTPSService {
-            service,
-            disabled: self.disabled,
-            replacements: self.replacements.clone(),
-        })
+    /// Attaches a [`RedirectControl`] handle, so redirection can be turned on and off at
+    /// runtime. Requests are only redirected while the handle is enabled and
+    /// [`set_enabled`](Self::set_enabled) has not disabled the middleware.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectControl, RedirectHTTPS};
+    ///
+    /// let control = RedirectControl::default();
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().control(&control));
+    /// control.set_enabled(false);
+    /// ```
+    pub fn control(mut self, control: &RedirectControl) -> Self {
+        self.options_mut().control = Some(control.clone());
+        self
     }
-}
 
-pub struct RedirectHTTPSService<S> {
-    service: S,
-    disabled: bool,
-    replacements: Vec<(String, String)>,
-}
+    /// Attaches a [`RedirectStats`] handle, which counts the redirects, pass-throughs, exemptions
+    /// and rejections of every middleware it is attached to.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, RedirectStats};
+    ///
+    /// let stats = RedirectStats::new();
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().stats(&stats));
+    /// assert_eq!(stats.redirects(), 0);
+    /// ```
+    pub fn stats(mut self, stats: &RedirectStats) -> Self {
+        self.options_mut().stats = Some(stats.clone());
+        self
+    }
 
-impl<S> Service<ServiceRequest> for RedirectHTTPSService<S>
-where
-    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
-    S::Future: 'static,
-{
-    type Response = ServiceResponse;
-    type Error = Error;
-    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+    /// Attaches a [`StatsdClient`], which sends the same counters as [`stats`](Self::stats) and
+    /// the time each decision took to a StatsD server.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, StatsdClient};
+    ///
+    /// let statsd = StatsdClient::new("127.0.0.1:8125", "web.redirect").unwrap();
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().statsd(&statsd));
+    /// ```
+    #[cfg(feature = "statsd")]
+    pub fn statsd(mut self, client: &StatsdClient) -> Self {
+        self.options_mut().statsd = Some(client.clone());
+        self
+    }
 
-    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
-        self.service.poll_ready(cx)
+    /// Redirects to the given `https` port, whatever port the `http` request was made to. Unlike
+    /// string replacements, only the port of the url is changed.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().to_port(8443));
+    /// ```
+    pub fn to_port(mut self, port: u16) -> Self {
+        self.options_mut().ports.to = Some(port);
+        self
     }
 
-    fn call(&self, req: ServiceRequest) -> Self::Future {
-        if req.connection_info().scheme() == "https" || self.disabled {
-            Either::Left(self.service.call(req))
-        } else {
-            let host = req.connection_info().host().to_owned();
-            let uri = req.uri().to_owned();
-            let mut url = format!("https://{}{}", host, uri);
-            for (s1, s2) in self.replacements.iter() {
-                url = url.replace(s1, s2);
-            }
-            Either::Right(ok(ServiceResponse::new(
-                req.into_parts().0,
-                HttpResponse::MovedPermanently()
-                    .insert_header((http::header::LOCATION, url))
-                    .finish()
-          )))
+    /// Redirects requests made to the `http` port `from` to the `https` port `to`. Requests
+    /// without an explicit port are treated as being made to port 80. Takes precedence over
+    /// [`to_port`](Self::to_port) and may be called several times.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().map_port(8080, 8443).map_port(80, 443));
+    /// ```
+    pub fn map_port(mut self, from: u16, to: u16) -> Self {
+        let map = &mut self.options_mut().ports.map;
+        map.retain(|(port, _)| *port != from);
+        map.push((from, to));
+        self
+    }
+
+    /// Redirects to the given canonical host, such as `example.com` for requests to
+    /// `www.example.com`, in the same redirect as the upgrade to `https`. Requests which already
+    /// arrived over `https` but for another host are redirected to the canonical host too. The
+    /// port of the request is kept unless `host` includes one.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().canonical_host("example.com"));
+    /// ```
+    pub fn canonical_host(mut self, host: &str) -> Self {
+        self.options_mut().canonical_host = Some(host.to_owned());
+        self
+    }
+
+    /// Redirects requests for the host `from` to the host `to`, keeping the path and query, such
+    /// that `http` traffic to a legacy domain lands on the new `https` domain in a single
+    /// redirect. Requests which already arrived over `https` for `from` are redirected too. The
+    /// port of the request is kept unless `to` includes one. Hosts are compared case
+    /// insensitively and without their port.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().rewrite_host("old.example.com", "new.example.com"));
+    /// ```
+    pub fn rewrite_host(mut self, from: &str, to: &str) -> Self {
+        let host_rewrites = &mut self.options_mut().host_rewrites;
+        host_rewrites.retain(|(host, _)| !host.eq_ignore_ascii_case(from));
+        host_rewrites.push((from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// Only reflects the given hosts into the redirect url, so a forged `Host` header cannot turn
+    /// the middleware into an open redirect. A host matches if it is equal to an entry, ignoring
+    /// case and port, or if the entry starts with `*.` and the host is a subdomain of the rest.
+    /// Requests for any other host get the
+    /// [`disallowed_host_response`](Self::disallowed_host_response), `400 Bad Request` by default.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().allowed_hosts(&["example.com", "*.example.com"]));
+    /// ```
+    pub fn allowed_hosts(mut self, hosts: &[&str]) -> Self {
+        self.options_mut().allowed_hosts = hosts.iter().map(|host| host.to_string()).collect();
+        self
+    }
+
+    /// Sets the host to redirect to when the request has no usable host, such as HTTP/1.0
+    /// requests without a `Host` header or requests with an empty or malformed one. Without a
+    /// fallback host, the host configured for the actix server is used.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().fallback_host("example.com"));
+    /// ```
+    pub fn fallback_host(mut self, host: &str) -> Self {
+        self.options_mut().fallback_host = Some(host.to_owned());
+        self
+    }
+
+    /// Responds with `400 Bad Request` instead of redirecting when the request has no usable
+    /// host and no [`fallback_host`](Self::fallback_host) is configured.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().reject_missing_host(true));
+    /// ```
+    pub fn reject_missing_host(mut self, reject: bool) -> Self {
+        self.options_mut().reject_missing_host = reject;
+        self
+    }
+
+    /// Sets the longest request path and query, in bytes, which is redirected. Longer requests
+    /// get `414 URI Too Long` instead, so they are not echoed into an equally long `Location`.
+    /// Defaults to 8 KiB.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().max_uri_length(2048));
+    /// ```
+    pub fn max_uri_length(mut self, length: usize) -> Self {
+        self.options_mut().max_uri_length = length;
+        self
+    }
+
+    /// Sets the response for `http` requests to hosts which are not in
+    /// [`allowed_hosts`](Self::allowed_hosts).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, HttpResponse};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .allowed_hosts(&["example.com"])
+    ///             .disallowed_host_response(|| HttpResponse::NotFound().body("Unknown host")),
+    ///     );
+    /// ```
+    pub fn disallowed_host_response<F>(mut self, response: F) -> Self
+    where
+        F: Fn() -> HttpResponse + Send + Sync + 'static,
+    {
+        self.options_mut().disallowed_host_response = Arc::new(response);
+        self
+    }
+
+    /// Sets the status of the empty response for `http` requests to hosts which are not in
+    /// [`allowed_hosts`](Self::allowed_hosts), such as `421 Misdirected Request` to tell clients
+    /// the server does not serve that host. This is a shorthand for
+    /// [`disallowed_host_response`](Self::disallowed_host_response).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{http::StatusCode, App};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .allowed_hosts(&["example.com"])
+    ///             .disallowed_host_status(StatusCode::MISDIRECTED_REQUEST),
+    ///     );
+    /// ```
+    pub fn disallowed_host_status(self, status_code: StatusCode) -> Self {
+        self.disallowed_host_response(move || HttpResponse::build(status_code).finish())
+    }
+
+    /// Calls `callback` with the request and the `Location` whenever a redirect is issued, such
+    /// as for custom logging, auditing or metrics. It runs before the response is sent, so it
+    /// should be quick.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().on_redirect(|req, location| {
+    ///         println!("redirecting {} to {}", req.path(), location);
+    ///     }));
+    /// ```
+    pub fn on_redirect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ServiceRequest, &str) + Send + Sync + 'static,
+    {
+        self.options_mut().on_redirect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Decides what to do with each request using custom rules, which replace the built-in
+    /// logic for every request the policy does not return [`RedirectDecision::Continue`] for.
+    /// Redirects still get the status code and headers configured on the middleware.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{dev::ServiceRequest, http::StatusCode, App};
+    /// use actix_web_middleware_redirect_https::{RedirectDecision, RedirectHTTPS, RedirectPolicy};
+    ///
+    /// struct Internal;
+    ///
+    /// impl RedirectPolicy for Internal {
+    ///     fn decide(&self, req: &ServiceRequest) -> RedirectDecision {
+    ///         match req.path() {
+    ///             "/internal" => RedirectDecision::Reject(StatusCode::NOT_FOUND),
+    ///             "/legacy" => RedirectDecision::Redirect("https://example.com/".parse().unwrap()),
+    ///             _ => RedirectDecision::Continue,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().policy(Internal));
+    /// ```
+    pub fn policy<P: RedirectPolicy + 'static>(mut self, policy: P) -> Self {
+        self.options_mut().policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Decides what to do with each request using custom rules which run asynchronously, such
+    /// as a per-customer flag looked up in a database. It runs after the [`policy`](Self::policy),
+    /// if any, returns [`RedirectDecision::Continue`].
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{dev::ServiceRequest, App};
+    /// use actix_web_middleware_redirect_https::{
+    ///     AsyncRedirectPolicy, RedirectDecision, RedirectHTTPS,
+    /// };
+    /// use futures::future::{FutureExt, LocalBoxFuture};
+    ///
+    /// struct Customers;
+    ///
+    /// impl AsyncRedirectPolicy for Customers {
+    ///     fn decide(&self, req: &ServiceRequest) -> LocalBoxFuture<'static, RedirectDecision> {
+    ///         let host = req.connection_info().host().to_owned();
+    ///         async move {
+    ///             // Look up whether the customer enforces https.
+    ///             match host.starts_with("legacy.") {
+    ///                 true => RedirectDecision::Pass,
+    ///                 false => RedirectDecision::Continue,
+    ///             }
+    ///         }
+    ///         .boxed_local()
+    ///     }
+    /// }
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().async_policy(Customers));
+    /// ```
+    pub fn async_policy<P: AsyncRedirectPolicy + 'static>(mut self, policy: P) -> Self {
+        self.options_mut().async_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Only redirects requests for which `predicate` returns `true`, passing the others through
+    /// to the application like other exemptions. This is a shorthand for simple rules which do
+    /// not need a [`policy`](Self::policy).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().redirect_if(|req| !req.path().starts_with("/api/")));
+    /// ```
+    pub fn redirect_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&ServiceRequest) -> bool + Send + Sync + 'static,
+    {
+        self.options_mut().redirect_if = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Handles requests for the given path and anything below it with another configuration,
+    /// such as one with different exclusions or status code for a scope. Paths match like
+    /// [`exclude_paths`](Self::exclude_paths), and the longest matching path wins.
+    ///
+    /// The configuration is used as it is, so options such as [`stats`](Self::stats) are not
+    /// inherited from this one.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{http::StatusCode, App};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().scope(
+    ///         "/api",
+    ///         RedirectHTTPS::default().status_code(StatusCode::PERMANENT_REDIRECT),
+    ///     ));
+    /// ```
+    pub fn scope(mut self, prefix: &str, config: RedirectHTTPS) -> Self {
+        self.options_mut()
+            .scopes
+            .push((prefix.to_owned(), config.options));
+        self
+    }
+
+    /// Redirects requests for the path `from` to the path `to`, such as `/login` to
+    /// `/secure/login`, in the same redirect as the upgrade to `https`. Paths are matched exactly,
+    /// without the query string, which is kept.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().rewrite_path("/login", "/secure/login"));
+    /// ```
+    pub fn rewrite_path(mut self, from: &str, to: &str) -> Self {
+        let path_rewrites = &mut self.options_mut().path_rewrites;
+        path_rewrites.retain(|(path, _)| path != from);
+        path_rewrites.push((from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// Normalizes the path of the redirect url, collapsing repeated slashes, resolving `.` and
+    /// `..` segments and handling the trailing slash like `NormalizePath` does, so clients land on
+    /// the canonical `https` url in one redirect rather than being redirected again by
+    /// `NormalizePath`. Path rewrites match the normalized path.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, middleware::TrailingSlash};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().normalize_path(TrailingSlash::Trim));
+    /// ```
+    pub fn normalize_path(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.options_mut().normalize_path = Some(trailing_slash);
+        self
+    }
+
+    /// Controls whether the path of the request is carried over to the redirect url. When
+    /// disabled every request is redirected to the root of the `https` site, or of the path
+    /// prefix. Combine with [`preserve_query`](Self::preserve_query) to drop the query string too.
+    /// Enabled by default.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().preserve_path(false).preserve_query(false));
+    /// ```
+    pub fn preserve_path(mut self, preserve: bool) -> Self {
+        self.options_mut().preserve_path = preserve;
+        self
+    }
+
+    /// Redirects every `http` request to the given url, regardless of its host, path and query,
+    /// which is useful for hard domain cutovers and decommissioned `http` endpoints. The url is
+    /// used as is, without any replacements.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().redirect_to("https://example.com/"));
+    /// ```
+    pub fn redirect_to(mut self, url: &str) -> Self {
+        self.options_mut().redirect_to = Some(url.to_owned());
+        self
+    }
+
+    /// Controls whether the query string of the request is carried over to the redirect url.
+    /// Disable it if query parameters, which may contain tokens, should not be reflected into
+    /// the `Location` header. Enabled by default.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().preserve_query(false));
+    /// ```
+    pub fn preserve_query(mut self, preserve: bool) -> Self {
+        self.options_mut().preserve_query = preserve;
+        self
+    }
+
+    /// Removes the given query parameters from the redirect url, so secrets accidentally sent
+    /// over `http` are not written into proxy logs and browser history again. Names are compared
+    /// case insensitively.
+    ///
+    /// With the `url` feature, the redirect url is parsed with the `url` crate and the parameters
+    /// are removed from its parsed pairs, which re-encodes the rest of the query.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().strip_query_params(&["token", "session", "api_key"]));
+    /// ```
+    pub fn strip_query_params(mut self, names: &[&str]) -> Self {
+        self.options_mut()
+            .stripped_query_params
+            .extend(names.iter().map(|name| name.to_string()));
+        self
+    }
+
+    /// Appends a query parameter, such as `upgraded=1`, to the redirect url, so the `https`
+    /// requests which came via the redirect can be told apart. Any query string of the request
+    /// is kept before it.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().marker_query_param("upgraded", "1"));
+    /// ```
+    pub fn marker_query_param(mut self, name: &str, value: &str) -> Self {
+        let encode =
+            |value| percent_encode(value, |b| b.is_ascii_alphanumeric() || b"-._~".contains(&b));
+        self.options_mut().marker_query_param = Some(format!("{}={}", encode(name), encode(value)));
+        self
+    }
+
+    /// Assembles the redirect url from a [`LocationTemplate`], such as
+    /// `https://{host}:{https_port}{path}{query}`, instead of `https://` followed by the host and
+    /// the path and query of the request. Replacements are still applied afterwards.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().location_template(
+    ///         "https://{host}:8443/app{path}{query}".parse().unwrap(),
+    ///     ));
+    /// ```
+    pub fn location_template(mut self, template: LocationTemplate) -> Self {
+        self.options_mut().location_template = Some(template);
+        self
+    }
+
+    /// Rewrites the redirect url with a regular expression, after any string replacements.
+    /// The replacement may refer to capture groups as `$1` or `${name}`, which makes layouts
+    /// possible that plain string replacement can't express, such as moving every tenant of
+    /// `old.com` to `new.com`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use regex::Regex;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().replace_regex(
+    ///         Regex::new(r"^https://(\w+)\.old\.com").unwrap(),
+    ///         "https://$1.new.com",
+    ///     ));
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn replace_regex(mut self, regex: Regex, replacement: &str) -> Self {
+        self.options_mut()
+            .regex_replacements
+            .push((regex, replacement.to_owned()));
+        self
+    }
+
+    /// Sets replacements which apply instead of the global ones, set with
+    /// [`with_replacements`](Self::with_replacements), when the request was made to the given
+    /// host. The host may be a `*.` wildcard pattern, and is matched without its port. Useful when
+    /// serving several domains from one `App`, each with different development ports.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .host_replacements("a.localhost", &[(":8080".to_owned(), ":8443".to_owned())])
+    ///             .host_replacements("b.localhost", &[(":8081".to_owned(), ":8444".to_owned())]),
+    ///     );
+    /// ```
+    pub fn host_replacements(mut self, host: &str, replacements: &[(String, String)]) -> Self {
+        self.options_mut().host_rule_mut(host).replacements = Replacements::new(replacements);
+        self
+    }
+
+    /// Maps the `http` port `from` to the `https` port `to` when the request was made to the
+    /// given host, instead of using the global port mappings. See
+    /// [`host_replacements`](Self::host_replacements) for how hosts are matched.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .host_map_port("a.localhost", 8080, 8443)
+    ///             .host_map_port("b.localhost", 8081, 8444),
+    ///     );
+    /// ```
+    pub fn host_map_port(mut self, host: &str, from: u16, to: u16) -> Self {
+        let ports = &mut self.options_mut().host_rule_mut(host).ports;
+        ports.map.retain(|(port, _)| *port != from);
+        ports.map.push((from, to));
+        self
+    }
+
+    /// Sets the scheme which requests are redirected from and the one they are redirected to,
+    /// `http` and `https` by default. Requests over the scheme redirected to are passed through,
+    /// subject to [`canonical_host`](Self::canonical_host), while requests over any other scheme
+    /// are passed through untouched. Port mappings treat a missing port as the default port of
+    /// `from`, and [`strip_default_port`](Self::strip_default_port) strips the default port of
+    /// `to`.
+    ///
+    /// Both schemes being the same is reported as [`ConfigError::SameScheme`] by
+    /// [`build`](RedirectHTTPSBuilder::build), and logged with the `logging` feature.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, Scheme};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().schemes(Scheme::Https, Scheme::Http).map_port(8443, 8080));
+    /// ```
+    pub fn schemes(mut self, from: Scheme, to: Scheme) -> Self {
+        let options = self.options_mut();
+        options.from_scheme = from;
+        options.to_scheme = to;
+        self
+    }
+
+    /// Controls whether an explicit default `https` port is removed from the redirect url, so a
+    /// mapping or replacement to port 443 results in `https://example.com/` rather than
+    /// `https://example.com:443/`. Enabled by default.
+    ///
+    /// With the `url` feature, parsing the url always omits a default port from the request or a
+    /// port mapping, so only default ports introduced by replacements can be kept.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().map_port(8080, 443).strip_default_port(false));
+    /// ```
+    pub fn strip_default_port(mut self, strip: bool) -> Self {
+        self.options_mut().strip_default_port = strip;
+        self
+    }
+
+    /// Controls whether the host is normalized before building the redirect url, so the
+    /// canonical `https` url does not depend on how the client typed the domain. The host is
+    /// lowercased and Unicode names are converted to punycode, such that `BÜCHER.example` is
+    /// redirected to `xn--bcher-kva.example`. Disabled by default.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().normalize_host(true));
+    /// ```
+    pub fn normalize_host(mut self, normalize: bool) -> Self {
+        self.options_mut().normalize_host = normalize;
+        self
+    }
+
+    /// Sets the status code used for the redirect response. Defaults to `301 Moved Permanently`,
+    /// which browsers cache aggressively, so `302 Found` or `307 Temporary Redirect` may be a
+    /// safer choice while rolling out.
+    ///
+    /// A status code other than a redirection (`3xx`) is reported as
+    /// [`ConfigError::InvalidStatusCode`] by [`build`](RedirectHTTPSBuilder::build), and logged with the
+    /// `logging` feature.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::StatusCode};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().status_code(StatusCode::TEMPORARY_REDIRECT));
+    /// ```
+    pub fn status_code(mut self, status_code: StatusCode) -> Self {
+        self.options_mut().status_code = status_code;
+        self
+    }
+
+    /// Redirects requests with methods other than `GET` and `HEAD` using the method preserving
+    /// counterpart of the configured status code, as recommended by RFC 7538. With the default
+    /// status code this means `GET` and `HEAD` requests get `301 Moved Permanently` and all others
+    /// get `308 Permanent Redirect`, so clients resend `POST` bodies instead of switching to `GET`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().preserve_method(true));
+    /// ```
+    pub fn preserve_method(mut self, preserve_method: bool) -> Self {
+        self.options_mut().preserve_method = preserve_method;
+        self
+    }
+
+    /// Rejects requests with methods other than `GET` and `HEAD` made over `http` with the given
+    /// status code, typically `403 Forbidden` or `405 Method Not Allowed`, instead of redirecting
+    /// them. `GET` and `HEAD` requests are still redirected. For APIs this surfaces clients
+    /// misconfigured to use `http`, which would otherwise silently follow the redirect.
+    ///
+    /// A status code other than a client error (`4xx`) is reported as
+    /// [`ConfigError::InvalidRejectStatusCode`] by [`build`](RedirectHTTPSBuilder::build), and logged
+    /// with the `logging` feature.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::StatusCode};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().reject_unsafe_methods(StatusCode::FORBIDDEN));
+    /// ```
+    pub fn reject_unsafe_methods(mut self, status_code: StatusCode) -> Self {
+        self.options_mut().reject_unsafe_methods = Some(status_code);
+        self
+    }
+
+    /// Responds to `http` requests with `426 Upgrade Required` and an
+    /// `Upgrade: TLS/1.2, HTTP/1.1` header instead of redirecting, for deployments that want
+    /// clients to explicitly retry over TLS rather than follow a redirect.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().upgrade_required(true));
+    /// ```
+    pub fn upgrade_required(mut self, upgrade_required: bool) -> Self {
+        self.options_mut().upgrade_required = upgrade_required;
+        self
+    }
+
+    /// Controls how WebSocket handshakes over `http`, which many WebSocket clients won't follow
+    /// a redirect for, are handled. By default they are redirected like any other request.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, WebSocketPolicy};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().websocket(WebSocketPolicy::Secure));
+    /// ```
+    pub fn websocket(mut self, policy: WebSocketPolicy) -> Self {
+        self.options_mut().websocket = policy;
+        self
+    }
+
+    /// Controls how CORS preflight requests over `http`, which are `OPTIONS` requests with an
+    /// `Origin` header, are handled. By default they are redirected like any other request, which
+    /// browsers won't follow. Preflights are handled before
+    /// [`reject_unsafe_methods`](Self::reject_unsafe_methods).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{PreflightPolicy, RedirectHTTPS};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().preflight(PreflightPolicy::Reject));
+    /// ```
+    pub fn preflight(mut self, policy: PreflightPolicy) -> Self {
+        self.options_mut().preflight = policy;
+        self
+    }
+
+    /// Sets how `http` requests without `Upgrade-Insecure-Requests: 1` are handled, so only
+    /// browsers which ask for `https` are redirected, while other clients are passed through or
+    /// rejected. Responses then carry `Vary: Upgrade-Insecure-Requests` so caches keep the two
+    /// apart. By default every request is redirected.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, UpgradeSignalPolicy};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().missing_upgrade_signal(UpgradeSignalPolicy::PassThrough));
+    /// ```
+    pub fn missing_upgrade_signal(mut self, policy: UpgradeSignalPolicy) -> Self {
+        self.options_mut().upgrade_signal = policy;
+        self
+    }
+
+    /// Sets `Cache-Control: max-age=...` on redirect responses, so repeat visitors skip the
+    /// `http` request. Without caching directives browsers differ in how long they remember
+    /// even permanent redirects.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use std::time::Duration;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().redirect_cache(Duration::from_secs(86400)));
+    /// ```
+    pub fn redirect_cache(mut self, max_age: Duration) -> Self {
+        self.options_mut().redirect_cache = Some(max_age);
+        self
+    }
+
+    /// Also sets an `Expires` header matching [`redirect_cache`](Self::redirect_cache), for
+    /// HTTP/1.0 caches which do not understand `Cache-Control`. Has no effect unless
+    /// `redirect_cache` is set, before or after this.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use std::time::Duration;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .redirect_cache(Duration::from_secs(86400))
+    ///             .redirect_expires(true),
+    ///     );
+    /// ```
+    pub fn redirect_expires(mut self, expires: bool) -> Self {
+        self.options_mut().redirect_expires = expires;
+        self
+    }
+
+    /// Caches the `Location` of up to `capacity` redirects per worker, keyed on the host, path
+    /// and query of the request, so repeated redirects for a small set of hot paths skip building
+    /// the url and applying replacements. The least recently used entry is evicted when the cache
+    /// is full, and the cache is cleared when the options are updated through
+    /// [`from_watch`](Self::from_watch) or [`from_config_watch`](Self::from_config_watch). A
+    /// capacity of zero disables the cache.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::with_replacements(&[(":8080".to_owned(), ":8443".to_owned())]).location_cache(1024));
+    /// ```
+    pub fn location_cache(mut self, capacity: usize) -> Self {
+        self.options_mut().location_cache = Some(capacity).filter(|capacity| *capacity > 0);
+        self
+    }
+
+    /// Appends headers, such as `X-Frame-Options`, to every redirect response.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::header::{self, HeaderValue}};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().with_headers(&[(
+    ///         header::X_FRAME_OPTIONS,
+    ///         HeaderValue::from_static("DENY"),
+    ///     )]));
+    /// ```
+    pub fn with_headers(mut self, headers: &[(HeaderName, HeaderValue)]) -> Self {
+        self.options_mut().extra_headers.extend_from_slice(headers);
+        self
+    }
+
+    /// Copies the `X-Request-Id` header of the request onto the responses of the middleware, such
+    /// as redirects, so they can be correlated with the rest of the request's trace. Requests
+    /// without one get a newly generated id.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().echo_request_id(true));
+    /// ```
+    pub fn echo_request_id(mut self, echo: bool) -> Self {
+        self.options_mut().request_id_header = Some(request_id::X_REQUEST_ID).filter(|_| echo);
+        self
+    }
+
+    /// Like [`echo_request_id`](Self::echo_request_id), but echoes the given correlation
+    /// header instead of `X-Request-Id`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::header::HeaderName};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().request_id_header(HeaderName::from_static("x-correlation-id")));
+    /// ```
+    pub fn request_id_header(mut self, header: HeaderName) -> Self {
+        self.options_mut().request_id_header = Some(header);
+        self
+    }
+
+    /// Sets a plain text body for the redirect response, which is otherwise empty. The
+    /// placeholders `{url}`, `{host}` and `{path}` are replaced with the redirect url, its host
+    /// and its path including the query string. Replaces [`html_body`](Self::html_body).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().body_template("Moved to {url}"));
+    /// ```
+    pub fn body_template(mut self, template: &str) -> Self {
+        self.options_mut().body = Body::Template(template.to_owned());
+        self
+    }
+
+    /// Sends a small HTML page along with the redirect, which links to the `https` url and
+    /// refreshes to it with `<meta http-equiv="refresh">`, for very old clients and embedded
+    /// browsers which ignore the `Location` header. Replaces
+    /// [`body_template`](Self::body_template).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().html_body(true));
+    /// ```
+    pub fn html_body(mut self, html: bool) -> Self {
+        self.options_mut().body = match html {
+            true => Body::Html,
+            false => Body::Empty,
+        };
+        self
+    }
+
+    /// Sends a JSON body such as `{"error":"https_required","location":"https://..."}` with
+    /// redirects and rejections of `http` requests when the client accepts
+    /// `application/json`, so API consumers get an actionable error rather than an empty
+    /// response. Other clients get the usual body.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().json_body(true));
+    /// ```
+    pub fn json_body(mut self, json: bool) -> Self {
+        self.options_mut().json_body = json;
+        self
+    }
+
+    /// Chooses the body of redirects and rejections of `http` requests by the `Accept` header,
+    /// among plain text, HTML and JSON variants. Renderers can be replaced or added with
+    /// [`body_renderer`](Self::body_renderer). Clients accepting none of the variants get the
+    /// usual body.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().negotiate_body(true));
+    /// ```
+    pub fn negotiate_body(mut self, negotiate: bool) -> Self {
+        let options = self.options_mut();
+        options.negotiation = match negotiate {
+            true => options
+                .negotiation
+                .take()
+                .or_else(|| Some(Negotiation::default())),
+            false => None,
+        };
+        self
+    }
+
+    /// Sets the renderer of the body for a media type, such as `text/html`, and enables
+    /// [`negotiate_body`](Self::negotiate_body). Renderers for media types other than the
+    /// built in `text/plain`, `text/html` and `application/json` are added after them.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, RedirectTarget};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().body_renderer("text/html", |target: &RedirectTarget| {
+    ///         format!("<a href=\"{}\">Continue to {}</a>", target.url(), target.host())
+    ///     }));
+    /// ```
+    pub fn body_renderer<F>(mut self, media_type: &str, render: F) -> Self
+    where
+        F: Fn(&RedirectTarget) -> String + Send + Sync + 'static,
+    {
+        self.options_mut()
+            .negotiation
+            .get_or_insert_with(Negotiation::default)
+            .set(media_type, Arc::new(render));
+        self
+    }
+
+    /// Adds a `Strict-Transport-Security` header with the given `max-age` to responses for
+    /// requests which arrived over `https`, unless the inner service already set one. The header
+    /// is never added to the `http` redirect itself, since browsers ignore it over plain `http`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().hsts(Duration::from_secs(31536000)));
+    /// ```
+    pub fn hsts(mut self, max_age: Duration) -> Self {
+        self.options_mut().hsts = Some(Hsts::new(max_age));
+        self
+    }
+
+    /// Like [`hsts`](Self::hsts), but with a full [`Hsts`] policy which may include the
+    /// `includeSubDomains` and `preload` directives. A policy which requests `preload` but does
+    /// not meet the preload list requirements is reported by [`build`](RedirectHTTPSBuilder::build), and
+    /// logged with the `logging` feature.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{Hsts, RedirectHTTPS};
+    ///
+    /// let hsts = Hsts::new(Duration::from_secs(63072000))
+    ///     .include_subdomains(true)
+    ///     .preload(true);
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::builder().hsts_policy(hsts).build().unwrap());
+    /// ```
+    pub fn hsts_policy(mut self, hsts: Hsts) -> Self {
+        self.options_mut().hsts = Some(hsts);
+        self
+    }
+
+    /// Adds `Report-To` and `NEL` headers to responses for requests which arrived over `https`,
+    /// unless the inner service already set them, so browsers report network errors, such as
+    /// failed TLS connections or downgrades, to the collector at `endpoint` for `max_age`. This
+    /// helps to find clients and links which still use `http`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().network_error_logging(
+    ///         "https://reports.example.com/nel",
+    ///         Duration::from_secs(86400),
+    ///     ));
+    /// ```
+    pub fn network_error_logging(mut self, endpoint: &str, max_age: Duration) -> Self {
+        self.options_mut().network_error_logging =
+            Some(NetworkErrorLogging::new(endpoint, max_age));
+        self
+    }
+
+    /// Adds an `Alt-Svc` header to responses for requests which arrived over `https`, unless the
+    /// inner service already set one, such as to advertise an HTTP/3 endpoint to clients.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::header::HeaderValue};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().alt_svc(HeaderValue::from_static("h3=\":443\"; ma=86400")));
+    /// ```
+    pub fn alt_svc(mut self, value: HeaderValue) -> Self {
+        self.options_mut().alt_svc = Some(value);
+        self
+    }
+
+    /// Adds an `Expect-CT` header with the given `max-age` to responses for requests which
+    /// arrived over `https`, unless the inner service already set one. With `enforce`, browsers
+    /// refuse connections whose certificates are not logged for Certificate Transparency, and
+    /// failures are reported to `report_uri` if given. Browsers no longer act on the header, but
+    /// some compliance requirements still ask for it.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::time::Duration;
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().expect_ct(
+    ///         Duration::from_secs(86400),
+    ///         true,
+    ///         Some("https://reports.example.com/ct"),
+    ///     ));
+    /// ```
+    pub fn expect_ct(mut self, max_age: Duration, enforce: bool, report_uri: Option<&str>) -> Self {
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if enforce {
+            value.push_str(", enforce");
+        }
+        if let Some(report_uri) = report_uri {
+            // Escaping quotes and control characters keeps the header value valid.
+            let report_uri = percent_encode(report_uri, is_url_byte);
+            value.push_str(&format!(", report-uri=\"{}\"", report_uri));
+        }
+        self.options_mut().expect_ct = HeaderValue::from_str(&value).ok();
+        self
+    }
+
+    /// Serves requests for the given paths, and anything below them, over `http` instead of
+    /// redirecting them, e.g. for health checks. `/healthz` matches `/healthz` and `/healthz/db`
+    /// but not `/healthzfoo`, while a prefix ending in a slash such as `/internal/` matches any
+    /// path starting with it.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exclude_paths(&["/healthz", "/internal/"]));
+    /// ```
+    pub fn exclude_paths(mut self, paths: &[&str]) -> Self {
+        self.options_mut().exclusions.add_prefixes(paths);
+        self
+    }
+
+    /// Serves ACME HTTP-01 challenge requests under `/.well-known/acme-challenge/` over `http`
+    /// instead of redirecting them, so certificate authorities such as Let's Encrypt can validate
+    /// the domain before a certificate exists. Disabled by default.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().allow_acme_challenges(true));
+    /// ```
+    pub fn allow_acme_challenges(mut self, allow: bool) -> Self {
+        self.options_mut().exclusions.acme_challenges = allow;
+        self
+    }
+
+    /// Answers ACME HTTP-01 challenges over `http` directly from a shared map of tokens to key
+    /// authorizations, which the application updates while ordering certificates. Unknown tokens
+    /// get `404 Not Found`. The inner service never sees challenge requests.
+    ///
+    /// ## Usage
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, RwLock};
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// let tokens = Arc::new(RwLock::new(HashMap::new()));
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().acme_tokens(tokens.clone()));
+    ///
+    /// // Later, when the ACME client receives a challenge:
+    /// tokens.write().unwrap().insert("token".to_owned(), "token.thumbprint".to_owned());
+    /// ```
+    pub fn acme_tokens(mut self, tokens: Arc<RwLock<HashMap<String, String>>>) -> Self {
+        self.options_mut().acme_responder = Some(AcmeResponder::Tokens(tokens));
+        self
+    }
+
+    /// Answers ACME HTTP-01 challenges over `http` from a webroot directory, as used by
+    /// `certbot --webroot`: the key authorization for a token is read from
+    /// `<webroot>/.well-known/acme-challenge/<token>`. Missing files get `404 Not Found`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().acme_webroot("/var/www/acme"));
+    /// ```
+    pub fn acme_webroot(mut self, webroot: impl Into<PathBuf>) -> Self {
+        self.options_mut().acme_responder = Some(AcmeResponder::Webroot(webroot.into()));
+        self
+    }
+
+    /// Serves requests whose path matches the given glob pattern over `http` instead of
+    /// redirecting them. `?` matches any single character and `*` any sequence of characters
+    /// within a path segment, while `**` also matches across segments, so `/static/**/*.map`
+    /// matches both `/static/app.js.map` and `/static/js/vendor/app.js.map`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exclude_glob("/static/**/*.map"));
+    /// ```
+    pub fn exclude_glob(mut self, pattern: &str) -> Self {
+        self.options_mut().exclusions.add_glob(pattern);
+        self
+    }
+
+    /// Serves the given health-check paths, such as `/healthz`, over `http` so load balancers
+    /// don't mark the backend unhealthy for answering with a redirect. Paths must match exactly,
+    /// and are either passed through to the application or answered with `200 OK` by the
+    /// middleware.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{HealthCheckPolicy, RedirectHTTPS};
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().health_checks(&["/healthz"], HealthCheckPolicy::Respond));
+    /// ```
+    pub fn health_checks(mut self, paths: &[&str], policy: HealthCheckPolicy) -> Self {
+        self.options_mut()
+            .exclusions
+            .add_health_checks(paths, policy);
+        self
+    }
+
+    /// Serves requests whose path matches the given regular expression over `http` instead of
+    /// redirecting them. The expression is matched against the path only, without the query
+    /// string, and should usually be anchored with `^` and `$`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use regex::Regex;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exclude_regex(Regex::new(r"^/api/v\d+/status$").unwrap()));
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn exclude_regex(mut self, regex: Regex) -> Self {
+        self.options_mut().exclusions.add_regex(regex);
+        self
+    }
+
+    /// Serves requests from clients within any of the given networks over `http` instead of
+    /// redirecting them, such as sidecars, health probes or a service mesh on loopback or private
+    /// ranges. The client is the peer address of the connection or, when the peer is one of the
+    /// [`trusted_proxies`](Self::trusted_proxies), the right-most address in `Forwarded` or
+    /// `X-Forwarded-For` which is not a trusted proxy, so clients cannot claim an exempt address
+    /// by sending the header themselves.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use ipnet::IpNet;
+    ///
+    /// let peers: Vec<IpNet> = vec!["127.0.0.0/8".parse().unwrap(), "10.0.0.0/8".parse().unwrap()];
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exempt_peers(&peers));
+    /// ```
+    pub fn exempt_peers(mut self, peers: &[IpNet]) -> Self {
+        self.options_mut().exempt_peers.extend_from_slice(peers);
+        self
+    }
+
+    /// Serves requests carrying the given header with the given secret value over `http`
+    /// instead of redirecting them, so internal tools can reach the `http` port directly. The
+    /// value is compared in constant time. Call this several times to accept several headers.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::{App, http::header::{HeaderName, HeaderValue}};
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exempt_header(
+    ///         HeaderName::from_static("x-internal-probe"),
+    ///         HeaderValue::from_static("s3cret"),
+    ///     ));
+    /// ```
+    pub fn exempt_header(mut self, name: HeaderName, secret: HeaderValue) -> Self {
+        self.options_mut().exempt_headers.push((name, secret));
+        self
+    }
+
+    /// Serves requests whose `User-Agent` starts with any of the given prefixes, such as
+    /// `kube-probe/`, over `http` instead of redirecting them. Since any client can send any
+    /// `User-Agent`, only use this for probes that cannot be exempted otherwise.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exempt_user_agents(&["kube-probe/"]));
+    /// ```
+    pub fn exempt_user_agents(mut self, prefixes: &[&str]) -> Self {
+        self.options_mut()
+            .exempt_user_agents
+            .extend(prefixes.iter().map(|prefix| prefix.to_string()));
+        self
+    }
+
+    /// Serves the health checks of cloud load balancers over `http`, since they can't follow
+    /// redirects. These are recognized by the `User-Agent` prefixes `GoogleHC/` for Google Cloud
+    /// and `ELB-HealthChecker/` for AWS. Since any client can send any `User-Agent`, prefer
+    /// [`exempt_peers`](Self::exempt_peers) with the ranges of the load balancer where possible.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exempt_cloud_health_checks(true));
+    /// ```
+    pub fn exempt_cloud_health_checks(mut self, exempt: bool) -> Self {
+        self.options_mut().exempt_cloud_health_checks = exempt;
+        self
+    }
+
+    /// Serves requests made to `localhost`, `127.0.0.1` or `[::1]`, on any port, over `http`
+    /// instead of redirecting them, so the same configuration works on developer machines without
+    /// TLS. Browsers only send these hosts for local servers, and already treat them as secure
+    /// contexts.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().exempt_localhost(true));
+    /// ```
+    pub fn exempt_localhost(mut self, exempt: bool) -> Self {
+        self.options_mut().exempt_localhost = exempt;
+        self
+    }
+
+    /// Takes the scheme of the request from the `X-Forwarded-Proto` header set by a TLS
+    /// terminating proxy, such as nginx or an AWS ALB, falling back to the scheme of the
+    /// connection itself when the header is missing.
+    ///
+    /// By default the middleware uses actix's `ConnectionInfo`, which consults the `Forwarded`
+    /// header before `X-Forwarded-Proto`. Once trust has been configured with this or a similar
+    /// method, only the explicitly trusted headers are consulted.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().trust_x_forwarded_proto(true));
+    /// ```
+    pub fn trust_x_forwarded_proto(mut self, trust: bool) -> Self {
+        self.options_mut()
+            .forwarding
+            .set_source(SchemeSource::XForwardedProto, trust);
+        self
+    }
+
+    /// Takes the scheme and host of the request from the standardized `Forwarded` header
+    /// (RFC 7239), e.g. `Forwarded: proto=https;host=example.com`. The client-most element of the
+    /// header is used, and the host is also used when building the redirect url.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().trust_forwarded(true));
+    /// ```
+    pub fn trust_forwarded(mut self, trust: bool) -> Self {
+        self.options_mut()
+            .forwarding
+            .set_source(SchemeSource::Forwarded, trust);
+        self
+    }
+
+    /// Takes the scheme of the request from the `CF-Visitor` header, e.g.
+    /// `CF-Visitor: {"scheme":"https"}`, which Cloudflare sends to the origin server. Combine this
+    /// with [`trusted_proxies`](Self::trusted_proxies) set to Cloudflare's published ranges so
+    /// the header cannot be spoofed by connecting to the origin directly.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().trust_cf_visitor(true));
+    /// ```
+    pub fn trust_cf_visitor(mut self, trust: bool) -> Self {
+        self.options_mut()
+            .forwarding
+            .set_source(SchemeSource::CfVisitor, trust);
+        self
+    }
+
+    /// Takes the host of the redirect url from the `X-Forwarded-Host` header, so the redirect goes
+    /// to the public host of the proxy rather than an internal one such as `backend:8080`.
+    ///
+    /// By default the host is taken from actix's `ConnectionInfo` until trust in a scheme source
+    /// is configured, after which only the `Host` header and a trusted `Forwarded` header are
+    /// used. Passing `false` ignores `X-Forwarded-Host` in either case.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .trust_x_forwarded_proto(true)
+    ///             .trust_x_forwarded_host(true),
+    ///     );
+    /// ```
+    pub fn trust_x_forwarded_host(mut self, trust: bool) -> Self {
+        self.options_mut().forwarding.x_forwarded_host = Some(trust);
+        self
+    }
+
+    /// Prefixes the path of the redirect url with the `X-Forwarded-Prefix` header, for
+    /// applications which a proxy serves under a subpath such as `/app`. The header takes
+    /// precedence over [`path_prefix`](Self::path_prefix).
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().trust_x_forwarded_prefix(true));
+    /// ```
+    pub fn trust_x_forwarded_prefix(mut self, trust: bool) -> Self {
+        self.options_mut().forwarding.x_forwarded_prefix = trust;
+        self
+    }
+
+    /// Prefixes the path of the redirect url with a fixed prefix, for applications which a proxy
+    /// serves under a subpath, e.g. `/app/login` for a request to `/login`.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().path_prefix("/app"));
+    /// ```
+    pub fn path_prefix(mut self, prefix: &str) -> Self {
+        self.options_mut().path_prefix = normalize_prefix(prefix);
+        self
+    }
+
+    /// Takes the port of the redirect url from the `X-Forwarded-Port` header, so the redirect
+    /// goes to the public port of the proxy rather than the local port of the application. The
+    /// default ports 80 and 443 are omitted from the url. Replacements are applied afterwards.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().trust_x_forwarded_port(true));
+    /// ```
+    pub fn trust_x_forwarded_port(mut self, trust: bool) -> Self {
+        self.options_mut().forwarding.x_forwarded_port = trust;
+        self
+    }
+
+    /// Sets the headers the scheme of the request is taken from, replacing any sources trusted
+    /// so far. The sources are consulted in order and the first one present on the request
+    /// decides, falling back to the scheme of the connection itself. An empty list means only the
+    /// connection is considered. Use [`SchemeSource::AppConfig`] to only consider whether the
+    /// listener itself uses TLS.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::{RedirectHTTPS, SchemeSource};
+    ///
+    /// // Azure App Service
+    /// App::new()
+    ///     .wrap(RedirectHTTPS::default().scheme_sources(&[
+    ///         SchemeSource::XArrSsl,
+    ///         SchemeSource::XForwardedProto,
+    ///     ]));
+    /// ```
+    pub fn scheme_sources(mut self, sources: &[SchemeSource]) -> Self {
+        self.options_mut().forwarding.set_sources(sources);
+        self
+    }
+
+    /// Only believes forwarded headers when the peer address of the connection is within one of
+    /// the given networks. Requests from any other peer are judged by the connection alone, so
+    /// clients cannot skip the redirect by sending `X-Forwarded-Proto: https` themselves.
+    ///
+    /// Unless [`forwarded_hops`](Self::forwarded_hops) is set, the trusted proxies are also used
+    /// to pick the right value out of chained headers such as `X-Forwarded-Proto: https, http`:
+    /// `X-Forwarded-For` is walked from the right while its addresses are trusted, and the value
+    /// added by the outermost trusted proxy is used.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    /// use ipnet::IpNet;
+    ///
+    /// let proxies: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+    ///
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .trust_x_forwarded_proto(true)
+    ///             .trusted_proxies(&proxies),
+    ///     );
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: &[IpNet]) -> Self {
+        self.options_mut().forwarding.trusted_proxies = proxies.to_vec();
+        self
+    }
+
+    /// Sets the number of proxies in front of the application which append to forwarded headers
+    /// such as `X-Forwarded-Proto: https, http`. The value added by the outermost of these proxies
+    /// is used, i.e. the `hops`-th value from the right, instead of the first value, which any
+    /// client could have sent. If the chain is shorter than `hops`, the first value is used.
+    ///
+    /// ## Usage
+    /// ```
+    /// use actix_web::App;
+    /// use actix_web_middleware_redirect_https::RedirectHTTPS;
+    ///
+    /// // A CDN in front of a load balancer.
+    /// App::new()
+    ///     .wrap(
+    ///         RedirectHTTPS::default()
+    ///             .trust_x_forwarded_proto(true)
+    ///             .forwarded_hops(2),
+    ///     );
+    /// ```
+    pub fn forwarded_hops(mut self, hops: usize) -> Self {
+        self.options_mut().forwarding.hops = Some(hops);
+        self
+    }
+}
+
+impl<State> RedirectHTTPS<State> {
+    /// Returns the options for changing, copying them first if they are shared.
+    fn options_mut(&mut self) -> &mut Options {
+        Arc::make_mut(&mut self.options)
+    }
+}
+
+impl Options {
+    /// Builds the `https` url the request should be redirected to. Requests which are already
+    /// `secure` are only redirected to change the host, so their port is left alone.
+    ///
+    /// Returns `None` if no valid absolute url can be built, such as for an invalid host.
+    fn location(&self, req: &ServiceRequest, secure: bool) -> Option<String> {
+        if let Some(url) = &self.redirect_to {
+            return Some(percent_encode(url, is_url_byte));
+        }
+        let mut host = self
+            .request_host(req)
+            .unwrap_or_else(|| req.app_config().host().to_owned());
+        let (replacements, ports) = match self.host_rule(&host) {
+            Some(rule) => (&rule.replacements, &rule.ports),
+            None => (&self.replacements, &self.ports),
+        };
+        if let Some(new_host) = self.host_rewrite(&host) {
+            host = replace_name(&host, new_host);
+        }
+        if let Some(canonical_host) = &self.canonical_host {
+            host = replace_name(&host, canonical_host);
+            if self.normalize_host {
+                host = normalize_host(&host).unwrap_or(host);
+            }
+        }
+        if !secure {
+            host = ports.apply(host, self.from_scheme.default_port());
+        }
+        let prefix = match self.forwarding.prefix(req) {
+            Some(prefix) => Cow::Owned(normalize_prefix(prefix)),
+            None => Cow::Borrowed(self.path_prefix.as_str()),
+        };
+        let normalized_path = self
+            .normalize_path
+            .map(|trailing_slash| normalize_path(req.path(), trailing_slash));
+        let request_path = normalized_path.as_deref().unwrap_or_else(|| req.path());
+        let path = match self.preserve_path {
+            true => self
+                .path_rewrites
+                .iter()
+                .find(|(from, _)| from == request_path)
+                .map_or(request_path, |(_, to)| to.as_str()),
+            false => "/",
+        };
+        let query = req.uri().query().filter(|_| self.preserve_query);
+        // With the `url` feature, the query is rewritten once the url is parsed.
+        #[cfg(feature = "url")]
+        let query = query.map(Cow::Borrowed);
+        #[cfg(not(feature = "url"))]
+        let query = self.query(query);
+        let query = query.as_deref();
+        let scheme = match self.websocket {
+            WebSocketPolicy::Secure if is_websocket(req) => self.to_scheme.websocket(),
+            _ => self.to_scheme,
+        };
+        let scheme = scheme.as_str();
+        let mut url = match &self.location_template {
+            Some(template) => {
+                let (name, https_port) = split_port(&host);
+                template.render(&LocationParts {
+                    host: name,
+                    https_port,
+                    default_port: &self.to_scheme.default_port().to_string(),
+                    path: &format!("{}{}", prefix, path),
+                    query,
+                })
+            }
+            None => {
+                // Assemble the path and query in a single buffer, sized up front.
+                let len = prefix.len() + path.len() + query.map_or(0, |query| query.len() + 1);
+                let mut path_and_query = String::with_capacity(len);
+                path_and_query.push_str(&prefix);
+                path_and_query.push_str(path);
+                if let Some(query) = query {
+                    path_and_query.push('?');
+                    path_and_query.push_str(query);
+                }
+                Uri::builder()
+                    .scheme(scheme)
+                    .authority(host.as_str())
+                    .path_and_query(percent_encode(path_and_query, is_url_byte))
+                    .build()
+                    .ok()?
+                    .to_string()
+            }
+        };
+        #[cfg(feature = "url")]
+        {
+            let marker = self.marker_query_param.as_deref();
+            url = parsed::rewrite(&url, &self.stripped_query_params, marker)?;
+        }
+        url = replacements.apply(url);
+        #[cfg(feature = "regex")]
+        for (regex, replacement) in &self.regex_replacements {
+            if let Cow::Owned(replaced) = regex.replace_all(&url, replacement.as_str()) {
+                url = replaced;
+            }
+        }
+        if self.strip_default_port {
+            strip_default_port(&mut url, self.to_scheme.default_port());
+        }
+        let url = percent_encode(url, is_url_byte);
+        // Replacements and templates may have broken the url, which must stay absolute.
+        let uri = url.parse::<Uri>().ok()?;
+        match uri.scheme().is_some() && uri.authority().is_some() {
+            true => Some(url),
+            false => None,
+        }
+    }
+
+    /// Removes the stripped parameters from the query of the request and appends the marker
+    /// parameter.
+    #[cfg(not(feature = "url"))]
+    fn query<'a>(&'a self, query: Option<&'a str>) -> Option<Cow<'a, str>> {
+        let query = match query {
+            Some(query) if !self.stripped_query_params.is_empty() => {
+                strip_query_params(query, &self.stripped_query_params).map(Cow::Owned)
+            }
+            query => query.map(Cow::Borrowed),
+        };
+        match (query, &self.marker_query_param) {
+            (Some(query), Some(marker)) => Some(Cow::Owned(format!("{}&{}", query, marker))),
+            (query, marker) => query.or_else(|| marker.as_deref().map(Cow::Borrowed)),
+        }
+    }
+
+    /// Returns the key of the `Location` of the redirect for the request in the location cache,
+    /// covering every part of the request which [`location`](Self::location) depends on.
+    fn cache_key(&self, req: &ServiceRequest, secure: bool) -> CacheKey {
+        CacheKey {
+            secure,
+            websocket: is_websocket(req),
+            host: self.request_host(req),
+            prefix: self.forwarding.prefix(req).map(str::to_owned),
+            path_and_query: req
+                .uri()
+                .path_and_query()
+                .map_or_else(String::new, |path_and_query| path_and_query.to_string()),
+        }
+    }
+
+    /// Returns the first rule for the host the request was made to, if any.
+    fn host_rule(&self, host: &str) -> Option<&HostRule> {
+        let (name, _) = split_port(host);
+        self.host_rules
+            .iter()
+            .find(|rule| host_matches(&rule.host, name))
+    }
+
+    /// Returns the rule for exactly the given host pattern, creating it if necessary.
+    fn host_rule_mut(&mut self, host: &str) -> &mut HostRule {
+        let index = match self.host_rules.iter().position(|rule| rule.host == host) {
+            Some(index) => index,
+            None => {
+                self.host_rules.push(HostRule {
+                    host: host.to_owned(),
+                    ..HostRule::default()
+                });
+                self.host_rules.len() - 1
+            }
+        };
+        &mut self.host_rules[index]
+    }
+
+    /// Returns the headers to add to responses for requests which arrived over `https`.
+    fn secure_headers(&self, req: &ServiceRequest) -> Vec<(HeaderName, HeaderValue)> {
+        let mut headers = Vec::new();
+        if let Some(hsts) = self
+            .hsts
+            .as_ref()
+            .and_then(|hsts| hsts.header_for(req.path()))
+        {
+            headers.push((header::STRICT_TRANSPORT_SECURITY, hsts));
+        }
+        if let Some(network_error_logging) = &self.network_error_logging {
+            headers.extend(network_error_logging.headers());
+        }
+        if let Some(alt_svc) = &self.alt_svc {
+            headers.push((header::ALT_SVC, alt_svc.clone()));
+        }
+        if let Some(expect_ct) = &self.expect_ct {
+            headers.push((EXPECT_CT, expect_ct.clone()));
+        }
+        headers
+    }
+
+    /// Counts an event in the attached [`RedirectStats`] and StatsD client, if any.
+    #[cfg_attr(not(feature = "sentry"), allow(unused_variables))]
+    fn count(&self, req: &ServiceRequest, event: Event) {
+        if let Some(stats) = &self.stats {
+            stats.count(event);
+        }
+        #[cfg(feature = "statsd")]
+        if let Some(statsd) = &self.statsd {
+            statsd.count(event);
+        }
+        #[cfg(feature = "sentry")]
+        if let Event::Rejection = event {
+            breadcrumbs::add(req, self.forwarding.host(req).as_deref(), "reject");
+        }
+    }
+
+    /// Returns whether redirection is currently turned off.
+    fn is_disabled(&self) -> bool {
+        self.disabled
+            || self
+                .control
+                .as_ref()
+                .is_some_and(|control| !control.is_enabled())
+    }
+
+    /// Returns whether redirection is turned off for this configuration and every scope within
+    /// it, and can only be turned back on through a control handle or an update.
+    fn is_off_for_good(&self) -> bool {
+        #[cfg(feature = "tokio")]
+        let fixed = self.control.is_none() && self.updates.is_none();
+        #[cfg(not(feature = "tokio"))]
+        let fixed = self.control.is_none();
+        fixed && self.disabled && self.scopes.iter().all(|(_, scope)| scope.is_off_for_good())
+    }
+
+    /// Returns the host the request was made to, or the fallback host if the request has no
+    /// usable host.
+    fn request_host(&self, req: &ServiceRequest) -> Option<String> {
+        self.forwarding
+            .host(req)
+            .map(|host| {
+                // Drop any credentials of an absolute-form request and bracket bare IPv6
+                // literals, which some proxies send.
+                let (name, port) = split_port(strip_userinfo(&host));
+                join_port(name, port)
+            })
+            .and_then(|host| match self.normalize_host {
+                true => normalize_host(&host),
+                false => Some(host),
+            })
+            .filter(|host| is_valid_host(host))
+            .or_else(|| self.fallback_host.clone())
+    }
+
+    /// Returns whether the request was made to a loopback host.
+    fn is_localhost(&self, req: &ServiceRequest) -> bool {
+        let host = match self.forwarding.host(req) {
+            Some(host) => host,
+            None => return false,
+        };
+        let (name, _) = split_port(strip_userinfo(&host));
+        ["localhost", "127.0.0.1", "[::1]", "::1"]
+            .iter()
+            .any(|localhost| name.eq_ignore_ascii_case(localhost))
+    }
+
+    /// Returns the host which requests to the given host are rewritten to, if any.
+    fn host_rewrite(&self, host: &str) -> Option<&str> {
+        let (name, _) = split_port(host);
+        self.host_rewrites
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(name))
+            .map(|(_, to)| to.as_str())
+    }
+
+    /// Returns whether the request was made to the canonical host, if one is configured, rather
+    /// than a host which is rewritten.
+    fn is_canonical(&self, req: &ServiceRequest) -> bool {
+        if !self.host_rewrites.is_empty() {
+            let host = self.request_host(req).unwrap_or_default();
+            if self.host_rewrite(&host).is_some() {
+                return false;
+            }
+        }
+        match &self.canonical_host {
+            Some(canonical_host) => {
+                let host = self.request_host(req).unwrap_or_default();
+                let (name, _) = split_port(&host);
+                let canonical_host = match self.normalize_host {
+                    true => {
+                        normalize_host(canonical_host).unwrap_or_else(|| canonical_host.clone())
+                    }
+                    false => canonical_host.clone(),
+                };
+                let (canonical_name, _) = split_port(&canonical_host);
+                name.eq_ignore_ascii_case(canonical_name)
+            }
+            None => true,
+        }
+    }
+
+    /// Returns whether the request is exempt from redirection, based on its path or client.
+    fn is_exempt(&self, req: &ServiceRequest) -> bool {
+        if self.exclusions.matches(req.path()) {
+            return true;
+        }
+        if self.exempt_localhost && self.is_localhost(req) {
+            return true;
+        }
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok());
+        if let Some(user_agent) = user_agent {
+            let mut prefixes = self.exempt_user_agents.iter().map(String::as_str);
+            if prefixes.any(|prefix| user_agent.starts_with(prefix)) {
+                return true;
+            }
+            if self.exempt_cloud_health_checks
+                && CLOUD_HEALTH_CHECKERS
+                    .iter()
+                    .any(|prefix| user_agent.starts_with(prefix))
+            {
+                return true;
+            }
+        }
+        let has_secret = self.exempt_headers.iter().any(|(name, secret)| {
+            req.headers()
+                .get_all(name)
+                .any(|value| constant_time_eq(value.as_bytes(), secret.as_bytes()))
+        });
+        if has_secret {
+            return true;
+        }
+        if self
+            .redirect_if
+            .as_ref()
+            .is_some_and(|predicate| !predicate(req))
+        {
+            return true;
+        }
+        !self.exempt_peers.is_empty()
+            && self
+                .forwarding
+                .client_ip(req)
+                .is_some_and(|ip| self.exempt_peers.iter().any(|net| net.contains(&ip)))
+    }
+
+    /// Returns the configuration for the scope the request was made to.
+    fn scoped<'a>(options: &'a Arc<Options>, req: &ServiceRequest) -> &'a Arc<Options> {
+        options
+            .scopes
+            .iter()
+            .filter(|(prefix, _)| matches_prefix(prefix, req.path()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(options, |(_, options)| options)
+    }
+
+    /// Returns whether the path and query of the request are too long to be redirected.
+    fn is_uri_too_long(&self, req: &ServiceRequest) -> bool {
+        let path_and_query = req.uri().path_and_query();
+        path_and_query.map_or(0, |path_and_query| path_and_query.as_str().len())
+            > self.max_uri_length
+    }
+
+    /// Returns whether the host of the request may be reflected into the redirect url.
+    fn is_allowed_host(&self, req: &ServiceRequest) -> bool {
+        if self.allowed_hosts.is_empty() {
+            return true;
+        }
+        let host = match self.request_host(req) {
+            Some(host) => host,
+            None => return false,
+        };
+        let (name, _) = split_port(&host);
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| host_matches(allowed, name))
+    }
+
+    /// Responds to the request with a redirect to `https`.
+    fn redirect(
+        &self,
+        req: ServiceRequest,
+        secure: bool,
+        cache: Option<&mut LocationCache>,
+    ) -> ServiceResponse {
+        let mut cache = cache.map(|cache| (cache, self.cache_key(&req, secure)));
+        let cached = cache.as_mut().and_then(|(cache, key)| cache.get(key));
+        let location = match cached {
+            Some(location) => location,
+            None => {
+                // The url is moved into the header value rather than copied.
+                let url = self.location(&req, secure).map(Bytes::from);
+                let location = url.and_then(|url| HeaderValue::from_maybe_shared(url).ok());
+                let location = match location {
+                    Some(location) => location,
+                    None => {
+                        #[cfg(feature = "logging")]
+                        logging::invalid_location(&req, self.forwarding.host(&req).as_deref());
+                        let res = HttpResponse::BadRequest().finish();
+                        return ServiceResponse::new(req.into_parts().0, res);
+                    }
+                };
+                if let Some((cache, key)) = cache {
+                    cache.insert(key, location.clone());
+                }
+                location
+            }
+        };
+        self.redirect_to(req, location, secure)
+    }
+
+    /// Returns the redirect to `location`, with the configured status code and headers.
+    fn redirect_to(
+        &self,
+        req: ServiceRequest,
+        location: HeaderValue,
+        secure: bool,
+    ) -> ServiceResponse {
+        let status_code = redirect_status(self.status_code, self.preserve_method, req.method());
+        let mut res = HttpResponse::build(status_code);
+        res.insert_header((header::LOCATION, location.clone()));
+        let hsts = self
+            .hsts
+            .as_ref()
+            .filter(|_| secure && self.to_scheme.is_secure());
+        if let Some(hsts) = hsts.and_then(|hsts| hsts.header_for(req.path())) {
+            res.insert_header((header::STRICT_TRANSPORT_SECURITY, hsts));
+        }
+        self.insert_vary(&mut res);
+        for header in &self.extra_headers {
+            res.append_header(header.clone());
+        }
+        if let Some(max_age) = self.redirect_cache {
+            res.insert_header(header::CacheControl(vec![header::CacheDirective::MaxAge(
+                max_age.as_secs().min(u32::MAX.into()) as u32,
+            )]));
+            if self.redirect_expires {
+                let expires = SystemTime::now() + max_age;
+                res.insert_header(header::Expires(expires.into()));
+            }
+        }
+        // Percent-encoding leaves only visible ASCII in the url, which always converts.
+        let url = location.to_str().unwrap_or_default();
+        if let Some(on_redirect) = &self.on_redirect {
+            on_redirect(&req, url);
+        }
+        self.count(&req, Event::Redirect);
+        #[cfg(feature = "sentry")]
+        if !secure {
+            breadcrumbs::add(&req, self.forwarding.host(&req).as_deref(), "redirect");
+        }
+        #[cfg(feature = "metrics")]
+        counters::redirect();
+        #[cfg(feature = "otel")]
+        {
+            let (scheme, reason) = match secure {
+                true => (self.to_scheme.as_str(), "canonical_host"),
+                false => (self.from_scheme.as_str(), "insecure_scheme"),
+            };
+            otel::redirect(&req, scheme, reason);
+        }
+        #[cfg(feature = "logging")]
+        logging::redirect(
+            &req,
+            self.forwarding.host(&req).as_deref(),
+            status_code,
+            url,
+        );
+        let target = RedirectTarget::new(url);
+        let res = self.respond(&req, res, &target, |res| self.body.respond(res, &target));
+        ServiceResponse::new(req.into_parts().0, res)
+    }
+
+    /// Returns the `426 Upgrade Required` response asking the client to retry over TLS.
+    fn upgrade(&self, req: ServiceRequest, message: Option<&str>) -> ServiceResponse {
+        let mut res = HttpResponse::build(StatusCode::UPGRADE_REQUIRED);
+        res.insert_header((header::UPGRADE, "TLS/1.2, HTTP/1.1"))
+            .insert_header((header::CONNECTION, "Upgrade"));
+        self.insert_vary(&mut res);
+        let url = match self.location(&req, false) {
+            Some(url) => url,
+            None => {
+                #[cfg(feature = "logging")]
+                logging::invalid_location(&req, self.forwarding.host(&req).as_deref());
+                let res = HttpResponse::BadRequest().finish();
+                return ServiceResponse::new(req.into_parts().0, res);
+            }
+        };
+        let target = match message {
+            Some(message) => RedirectTarget::new(&url).with_message(message),
+            None => RedirectTarget::new(&url),
+        };
+        let res = self.respond(&req, res, &target, |mut res| match message {
+            Some(message) => res.content_type("text/plain").body(message.to_owned()),
+            None => res.finish(),
+        });
+        ServiceResponse::new(req.into_parts().0, res)
+    }
+
+    /// Returns the response rejecting the request, if its method is not allowed over `http`.
+    fn rejection(&self, req: &ServiceRequest) -> Option<HttpResponse> {
+        let status_code = self.reject_unsafe_methods?;
+        if matches!(*req.method(), Method::GET | Method::HEAD) {
+            return None;
+        }
+        let mut res = HttpResponse::build(status_code);
+        if status_code == StatusCode::METHOD_NOT_ALLOWED {
+            res.insert_header((header::ALLOW, "GET, HEAD"));
+        }
+        self.insert_vary(&mut res);
+        let message = format!(
+            "{} requests must be made over {}",
+            req.method(),
+            self.to_scheme.as_str()
+        );
+        let url = match self.location(req, false) {
+            Some(url) => url,
+            None => {
+                #[cfg(feature = "logging")]
+                logging::invalid_location(req, self.forwarding.host(req).as_deref());
+                return Some(HttpResponse::BadRequest().finish());
+            }
+        };
+        let target = RedirectTarget::new(&url).with_message(&message);
+        Some(self.respond(req, res, &target, |mut res| {
+            res.content_type("text/plain").body(message.clone())
+        }))
+    }
+
+    /// Sets `Vary` to the request headers the response depends on, so caches do not serve it
+    /// to requests which would have been treated differently.
+    fn insert_vary(&self, res: &mut HttpResponseBuilder) {
+        let mut headers = self.forwarding.vary_headers();
+        if self.negotiation.is_some() || self.json_body {
+            headers.push(header::ACCEPT);
+        }
+        if !self.exempt_user_agents.is_empty() || self.exempt_cloud_health_checks {
+            headers.push(header::USER_AGENT);
+        }
+        if self.upgrade_signal != UpgradeSignalPolicy::Redirect {
+            headers.push(header::UPGRADE_INSECURE_REQUESTS);
+        }
+        if !headers.is_empty() {
+            let headers: Vec<&str> = headers.iter().map(HeaderName::as_str).collect();
+            res.insert_header((header::VARY, headers.join(", ")));
+        }
+    }
+
+    /// Finishes a response to an `http` request with the echoed request id, if enabled, and the
+    /// body the client asked for, or the given default body.
+    fn respond<F>(
+        &self,
+        req: &ServiceRequest,
+        mut res: HttpResponseBuilder,
+        target: &RedirectTarget,
+        default: F,
+    ) -> HttpResponse
+    where
+        F: FnOnce(HttpResponseBuilder) -> HttpResponse,
+    {
+        if let Some(name) = &self.request_id_header {
+            let id = req
+                .headers()
+                .get(name)
+                .cloned()
+                .or_else(|| HeaderValue::from_str(&request_id::generate()).ok());
+            if let Some(id) = id {
+                res.insert_header((name.clone(), id));
+            }
+        }
+        let mut res = match &self.negotiation {
+            Some(negotiation) => match negotiation.respond(req, res, target) {
+                Ok(res) => return res,
+                Err(res) => res,
+            },
+            None => res,
+        };
+        if self.json_body && accepts_json(req) {
+            return res
+                .content_type("application/json")
+                .body(json_error(target.url(), target.message()));
+        }
+        default(res)
+    }
+
+    /// Checks that the options make sense together.
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.status_code.is_redirection() {
+            return Err(ConfigError::InvalidStatusCode(self.status_code));
+        }
+        if let Some(status_code) = self.reject_unsafe_methods {
+            if !status_code.is_client_error() {
+                return Err(ConfigError::InvalidRejectStatusCode(status_code));
+            }
+        }
+        if self.from_scheme == self.to_scheme {
+            return Err(ConfigError::SameScheme(self.from_scheme));
+        }
+        if let Some(hsts) = &self.hsts {
+            hsts.validate()?;
+        }
+        for (_, options) in &self.scopes {
+            options.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the `414 URI Too Long` response for requests over the maximum length.
+fn uri_too_long(req: ServiceRequest) -> ServiceResponse {
+    let res = HttpResponse::build(StatusCode::URI_TOO_LONG).finish();
+    ServiceResponse::new(req.into_parts().0, res)
+}
+
+/// Returns whether a host is usable as the authority of a url: not empty and without userinfo.
+pub(crate) fn is_valid_host(host: &str) -> bool {
+    !host.is_empty() && !host.contains('@') && host.parse::<Authority>().is_ok()
+}
+
+/// Ensures a path prefix starts with a slash and does not end with one, so it can be put in front
+/// of the request path as is.
+fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", percent_encode(prefix, is_path_byte))
+    }
+}
+
+/// Returns the status code which should be used to redirect a request with the given method.
+fn redirect_status(status_code: StatusCode, preserve_method: bool, method: &Method) -> StatusCode {
+    if !preserve_method || method == Method::GET || method == Method::HEAD {
+        return status_code;
+    }
+    match status_code {
+        StatusCode::MOVED_PERMANENTLY => StatusCode::PERMANENT_REDIRECT,
+        StatusCode::FOUND | StatusCode::SEE_OTHER => StatusCode::TEMPORARY_REDIRECT,
+        other => other,
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RedirectHTTPS
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RedirectHTTPSService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        let options = &self.options;
+        let killed = env::is_killed(std::env::var(env::KILL_SWITCH).ok());
+        #[cfg(feature = "logging")]
+        {
+            if let Err(error) = options.validate() {
+                logging::invalid_config(&error);
+            }
+            if killed {
+                logging::killed();
+            }
+        }
+        ok(RedirectHTTPSService {
+            service: Rc::new(service),
+            config: Rc::new(RefCell::new(options.clone())),
+            #[cfg(feature = "tokio")]
+            updates: Rc::new(RefCell::new(options.updates.clone())),
+            cache: Rc::new(RefCell::new(options.location_cache.map(LocationCache::new))),
+            disabled: killed || options.is_off_for_good(),
+        })
+    }
+}
+
+pub struct RedirectHTTPSService<S> {
+    service: Rc<S>,
+    config: Rc<RefCell<Arc<Options>>>,
+    #[cfg(feature = "tokio")]
+    updates: Rc<RefCell<Option<Updates>>>,
+    /// The `Location` values this worker has built, if caching is enabled.
+    cache: Rc<RefCell<Option<LocationCache>>>,
+    /// Whether the middleware is disabled for good, by the `FORCE_HTTPS` kill switch or by
+    /// options which cannot change, so requests are only marked with their scheme and passed
+    /// through.
+    disabled: bool,
+}
+
+// The state is shared with the futures of asynchronous policies, which decide after `call`.
+impl<S> Clone for RedirectHTTPSService<S> {
+    fn clone(&self) -> Self {
+        RedirectHTTPSService {
+            service: Rc::clone(&self.service),
+            config: Rc::clone(&self.config),
+            #[cfg(feature = "tokio")]
+            updates: Rc::clone(&self.updates),
+            cache: Rc::clone(&self.cache),
+            disabled: self.disabled,
+        }
+    }
+}
+
+impl<S> RedirectHTTPSService<S> {
+    /// Switches to the latest configuration sent to [`RedirectHTTPS::from_watch`] or
+    /// [`RedirectHTTPS::from_config_watch`], if any.
+    #[cfg(feature = "tokio")]
+    fn update(&self) {
+        let options = self
+            .updates
+            .borrow_mut()
+            .as_mut()
+            .and_then(Updates::changed);
+        if let Some(options) = options {
+            *self.cache.borrow_mut() = options.location_cache.map(LocationCache::new);
+            *self.config.borrow_mut() = options;
+        }
+    }
+}
+
+/// Passes a response from the application through, in the left body.
+type PassThrough<B> = fn(ServiceResponse<B>) -> ServiceResponse<EitherBody<B>>;
+
+impl<S, B> RedirectHTTPSService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    /// Passes the request through to the application.
+    fn pass(&self, req: ServiceRequest) -> <Self as Service<ServiceRequest>>::Future {
+        #[cfg(feature = "tracing")]
+        trace::record("pass");
+        let pass: PassThrough<B> = ServiceResponse::map_into_left_body;
+        Either::Left(self.service.call(req).map_ok(pass))
+    }
+
+    /// Decides what to do with a request while redirection is enabled.
+    fn decide(
+        &self,
+        config: &Arc<Options>,
+        req: ServiceRequest,
+        secure: bool,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        #[cfg(feature = "statsd")]
+        let timer = config.statsd.as_ref().map(DecisionTimer::start);
+        if req.extensions().contains::<SkipHttpsRedirect>() {
+            if !config.from_scheme.matches(&req, secure) {
+                return self.pass(req);
+            }
+            config.count(&req, Event::Exemption);
+            return self.pass_insecure(config, req);
+        }
+        let decision = config.policy.as_ref().map(|policy| policy.decide(&req));
+        if let Some(decision) = decision.filter(|d| *d != RedirectDecision::Continue) {
+            return self.apply(config, req, decision, secure);
+        }
+        if let Some(policy) = &config.async_policy {
+            let decision = policy.decide(&req);
+            let (this, config) = (self.clone(), Arc::clone(config));
+            let fut = async move {
+                let fut = match decision.await {
+                    RedirectDecision::Continue => this.decide_builtin(&config, req, secure),
+                    decision => this.apply(&config, req, decision, secure),
+                };
+                // The decision is timed until here, leaving out the application.
+                #[cfg(feature = "statsd")]
+                drop(timer);
+                fut.await
+            };
+            #[cfg(feature = "tracing")]
+            let fut = tracing::Instrument::in_current_span(fut);
+            return Either::Right(Box::pin(fut));
+        }
+        self.decide_builtin(config, req, secure)
+    }
+
+    /// Decides what to do with a request using the built-in logic.
+    fn decide_builtin(
+        &self,
+        config: &Options,
+        req: ServiceRequest,
+        secure: bool,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        if !config.from_scheme.matches(&req, secure) {
+            if !config.to_scheme.matches(&req, secure) {
+                return self.pass(req);
+            }
+            if !config.is_canonical(&req) {
+                if config.is_uri_too_long(&req) {
+                    return Self::respond(uri_too_long(req));
+                }
+                return Self::respond(config.redirect(req, true, self.cache.borrow_mut().as_mut()));
+            }
+            match secure {
+                true => self.pass_secure(config, req),
+                false => self.pass(req),
+            }
+        } else if let Some((responder, token)) = config
+            .acme_responder
+            .as_ref()
+            .zip(AcmeResponder::token(req.path()))
+        {
+            #[cfg(feature = "tracing")]
+            trace::record("acme");
+            let (responder, token) = (responder.clone(), token.to_owned());
+            Either::Right(Box::pin(async move {
+                let res = responder.respond(token).await;
+                Ok(ServiceResponse::new(req.into_parts().0, res).map_into_right_body())
+            }))
+        } else if let Some(policy) = config.exclusions.health_check(req.path()) {
+            match policy {
+                HealthCheckPolicy::Respond => {
+                    let res = HttpResponse::Ok().finish();
+                    Self::respond(ServiceResponse::new(req.into_parts().0, res))
+                }
+                _ => self.pass_insecure(config, req),
+            }
+        } else if config.is_exempt(&req) {
+            config.count(&req, Event::Exemption);
+            self.pass_insecure(config, req)
+        } else if config.preflight != PreflightPolicy::Redirect && is_preflight(&req) {
+            let res = match config.preflight {
+                PreflightPolicy::Reject => {
+                    config.count(&req, Event::Rejection);
+                    HttpResponse::Forbidden().finish()
+                }
+                PreflightPolicy::Respond => HttpResponse::NoContent().finish(),
+                _ => return self.pass_insecure(config, req),
+            };
+            Self::respond(ServiceResponse::new(req.into_parts().0, res))
+        } else if config.upgrade_signal != UpgradeSignalPolicy::Redirect
+            && !sends_upgrade_signal(&req)
+        {
+            let vary = HeaderValue::from_static("upgrade-insecure-requests");
+            match config.upgrade_signal {
+                UpgradeSignalPolicy::Reject => {
+                    config.count(&req, Event::Rejection);
+                    let res = HttpResponse::Forbidden()
+                        .insert_header((header::VARY, vary))
+                        .finish();
+                    Self::respond(ServiceResponse::new(req.into_parts().0, res))
+                }
+                _ => self.pass_varying(config, req, vary),
+            }
+        } else if config.is_uri_too_long(&req) {
+            config.count(&req, Event::Rejection);
+            Self::respond(uri_too_long(req))
+        } else if config.reject_missing_host && config.request_host(&req).is_none() {
+            config.count(&req, Event::Rejection);
+            let res = HttpResponse::BadRequest().finish();
+            Self::respond(ServiceResponse::new(req.into_parts().0, res))
+        } else if !config.is_allowed_host(&req) {
+            config.count(&req, Event::Rejection);
+            let res = (config.disallowed_host_response)();
+            Self::respond(ServiceResponse::new(req.into_parts().0, res))
+        } else if let Some(res) = config.rejection(&req) {
+            // The host was checked above, since the rejection includes the url built from it.
+            config.count(&req, Event::Rejection);
+            #[cfg(feature = "metrics")]
+            counters::rejection();
+            Self::respond(ServiceResponse::new(req.into_parts().0, res))
+        } else if config.websocket == WebSocketPolicy::Reject && is_websocket(&req) {
+            config.count(&req, Event::Rejection);
+            let message = format!(
+                "WebSocket connections must use {}",
+                config.to_scheme.websocket().as_str()
+            );
+            Self::respond(config.upgrade(req, Some(&message)))
+        } else if config.upgrade_required {
+            Self::respond(config.upgrade(req, None))
+        } else {
+            Self::respond(config.redirect(req, false, self.cache.borrow_mut().as_mut()))
+        }
+    }
+
+    /// Carries out the decision of a [`RedirectPolicy`].
+    fn apply(
+        &self,
+        config: &Options,
+        req: ServiceRequest,
+        decision: RedirectDecision,
+        secure: bool,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        match decision {
+            RedirectDecision::Redirect(url) => {
+                let location = HeaderValue::from_maybe_shared(Bytes::from(url.to_string()));
+                match location {
+                    Ok(location) => Self::respond(config.redirect_to(req, location, secure)),
+                    Err(_) => {
+                        let res = HttpResponse::BadRequest().finish();
+                        Self::respond(ServiceResponse::new(req.into_parts().0, res))
+                    }
+                }
+            }
+            RedirectDecision::Reject(status_code) => {
+                config.count(&req, Event::Rejection);
+                let res = HttpResponse::build(status_code).finish();
+                Self::respond(ServiceResponse::new(req.into_parts().0, res))
+            }
+            _ if config.from_scheme.matches(&req, secure) => self.pass_insecure(config, req),
+            _ if secure && config.to_scheme.matches(&req, secure) => self.pass_secure(config, req),
+            _ => self.pass(req),
+        }
+    }
+
+    /// Passes an `https` request on to the inner service, adding the
+    /// [`secure_headers`](Options::secure_headers) which the response does not already have.
+    fn pass_secure(
+        &self,
+        config: &Options,
+        req: ServiceRequest,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        let headers = config.secure_headers(&req);
+        if headers.is_empty() {
+            return self.pass(req);
+        }
+        #[cfg(feature = "tracing")]
+        trace::record("pass");
+        let fut = self.service.call(req);
+        Either::Right(Box::pin(async move {
+            let mut res = fut.await?;
+            let res_headers = res.headers_mut();
+            for (name, value) in headers {
+                if !res_headers.contains_key(&name) {
+                    res_headers.insert(name, value);
+                }
+            }
+            Ok(res.map_into_left_body())
+        }))
+    }
+
+    /// Passes an `http` request on to the inner service instead of redirecting it.
+    fn pass_insecure(
+        &self,
+        config: &Options,
+        req: ServiceRequest,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        config.count(&req, Event::PassThrough);
+        #[cfg(feature = "metrics")]
+        counters::pass();
+        self.pass(req)
+    }
+
+    /// Like [`pass_insecure`](Self::pass_insecure), but adds `vary` to the `Vary` header of the
+    /// response, since the request would have been redirected with other headers.
+    fn pass_varying(
+        &self,
+        config: &Options,
+        req: ServiceRequest,
+        vary: HeaderValue,
+    ) -> <Self as Service<ServiceRequest>>::Future {
+        config.count(&req, Event::PassThrough);
+        #[cfg(feature = "metrics")]
+        counters::pass();
+        #[cfg(feature = "tracing")]
+        trace::record("pass");
+        let fut = self.service.call(req);
+        Either::Right(Box::pin(async move {
+            let mut res = fut.await?;
+            res.headers_mut().append(header::VARY, vary);
+            Ok(res.map_into_left_body())
+        }))
+    }
+
+    /// Responds with a response generated by the middleware, in the right body.
+    fn respond(res: ServiceResponse) -> <Self as Service<ServiceRequest>>::Future {
+        #[cfg(feature = "tracing")]
+        trace::record_response(res.response());
+        Either::Right(Box::pin(ok(res.map_into_right_body())))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RedirectHTTPSService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Either<
+        MapOk<S::Future, PassThrough<B>>,
+        LocalBoxFuture<'static, Result<Self::Response, Self::Error>>,
+    >;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        #[cfg(feature = "tokio")]
+        self.update();
+        let config = &*self.config.borrow();
+        // Nested instances are told apart by the configuration they share with their clones.
+        let id = Arc::as_ptr(config) as usize;
+        if Handled::contains(&req, id) {
+            return self.pass(req);
+        }
+        let config = Options::scoped(config, &req);
+        let secure = config.forwarding.is_secure(&req);
+        // Guards and extractors rely on the scheme even while redirection is off.
+        Handled::insert(&req, id, secure);
+        if self.disabled || config.is_disabled() {
+            return self.pass(req);
         }
+        #[cfg(feature = "tracing")]
+        let _span = trace::span(&req, &config.forwarding).entered();
+        self.decide(config, req, secure)
     }
 }
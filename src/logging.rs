@@ -0,0 +1,50 @@
+//! Records of redirects and configuration issues for the `log` crate.
+
+use crate::ConfigError;
+use actix_web::{dev::ServiceRequest, http::StatusCode};
+
+/// The target of every record.
+const TARGET: &str = "redirect_https";
+
+/// Records a redirect at the info level.
+pub(crate) fn redirect(
+    req: &ServiceRequest,
+    host: Option<&str>,
+    status_code: StatusCode,
+    location: &str,
+) {
+    log::info!(
+        target: TARGET,
+        peer:? = req.peer_addr(),
+        host = host.unwrap_or_default(),
+        path = req.path(),
+        status = status_code.as_u16(),
+        location = location;
+        "redirecting {} to {} with {}",
+        req.path(),
+        location,
+        status_code
+    );
+}
+
+/// Warns that no valid redirect url could be built for a request.
+pub(crate) fn invalid_location(req: &ServiceRequest, host: Option<&str>) {
+    log::warn!(
+        target: TARGET,
+        peer:? = req.peer_addr(),
+        host = host.unwrap_or_default(),
+        path = req.path();
+        "no valid redirect url could be built for {}, check the host, replacements and template",
+        req.path()
+    );
+}
+
+/// Warns about options which do not make sense together.
+pub(crate) fn invalid_config(error: &ConfigError) {
+    log::warn!(target: TARGET, "invalid redirect configuration: {}", error);
+}
+
+/// Notes that the `FORCE_HTTPS` kill switch turned redirection off.
+pub(crate) fn killed() {
+    log::info!(target: TARGET, "redirection is turned off by the FORCE_HTTPS environment variable");
+}
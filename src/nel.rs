@@ -0,0 +1,40 @@
+use crate::body::escape_json;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use std::time::Duration;
+
+const REPORT_TO: HeaderName = HeaderName::from_static("report-to");
+const NEL: HeaderName = HeaderName::from_static("nel");
+
+/// The name of the reporting group the `NEL` policy sends reports to.
+const GROUP: &str = "network-errors";
+
+/// The `Report-To` and `NEL` headers asking browsers to report network errors, such as failed
+/// TLS connections, to a collector.
+#[derive(Debug, Clone)]
+pub(crate) struct NetworkErrorLogging {
+    report_to: HeaderValue,
+    nel: HeaderValue,
+}
+
+impl NetworkErrorLogging {
+    pub(crate) fn new(endpoint: &str, max_age: Duration) -> Self {
+        let max_age = max_age.as_secs();
+        let report_to = format!(
+            "{{\"group\":\"{}\",\"max_age\":{},\"endpoints\":[{{\"url\":\"{}\"}}]}}",
+            GROUP,
+            max_age,
+            escape_json(endpoint)
+        );
+        let nel = format!("{{\"report_to\":\"{}\",\"max_age\":{}}}", GROUP, max_age);
+        NetworkErrorLogging {
+            // Escaping leaves no control characters, so the values always convert.
+            report_to: HeaderValue::from_str(&report_to).expect("escaped JSON is a header value"),
+            nel: HeaderValue::from_str(&nel).expect("escaped JSON is a header value"),
+        }
+    }
+
+    /// Returns the headers to add to `https` responses.
+    pub(crate) fn headers(&self) -> [(HeaderName, HeaderValue); 2] {
+        [(REPORT_TO, self.report_to.clone()), (NEL, self.nel.clone())]
+    }
+}
@@ -0,0 +1,20 @@
+//! Attributes on the active OpenTelemetry span.
+
+use actix_web::dev::ServiceRequest;
+use opentelemetry::{trace::get_active_span, KeyValue};
+
+/// Describes a redirect on the active span: the scheme the request was made over, its target
+/// and why it was redirected, `insecure_scheme` or `canonical_host`.
+pub(crate) fn redirect(req: &ServiceRequest, scheme: &'static str, reason: &'static str) {
+    let target = req.uri().path_and_query().map_or_else(
+        || req.path().to_owned(),
+        |target| target.as_str().to_owned(),
+    );
+    get_active_span(|span| {
+        span.set_attributes([
+            KeyValue::new("http.scheme", scheme),
+            KeyValue::new("http.target", target),
+            KeyValue::new("redirect.reason", reason),
+        ]);
+    });
+}
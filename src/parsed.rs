@@ -0,0 +1,31 @@
+use url::Url;
+
+/// Parses the redirect url with the `url` crate and rewrites its query on the parsed pairs,
+/// removing the parameters with the given names, compared case insensitively, and appending the
+/// marker parameter. Parsing also normalizes the host and omits the default port of the scheme.
+///
+/// Returns `None` if the url cannot be parsed.
+pub(crate) fn rewrite(url: &str, stripped: &[String], marker: Option<&str>) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    if !stripped.is_empty() && url.query().is_some() {
+        let kept: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !stripped.iter().any(|s| s.eq_ignore_ascii_case(name)))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        match kept.is_empty() {
+            true => url.set_query(None),
+            false => {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+    }
+    if let Some(marker) = marker {
+        let query = match url.query() {
+            Some(query) => format!("{}&{}", query, marker),
+            None => marker.to_owned(),
+        };
+        url.set_query(Some(&query));
+    }
+    Some(url.into())
+}
@@ -0,0 +1,54 @@
+use actix_web::{
+    dev::ServiceRequest,
+    http::{StatusCode, Uri},
+};
+use futures::future::LocalBoxFuture;
+
+/// What to do with a request, as decided by a [`RedirectPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RedirectDecision {
+    /// Redirects to the url, with the status code, `Strict-Transport-Security` and other headers
+    /// of the middleware's own redirects.
+    Redirect(Uri),
+    /// Passes the request through to the application, adding `Strict-Transport-Security` and
+    /// the other headers of the middleware's `https` responses if it arrived over `https`.
+    Pass,
+    /// Responds with the status code, without calling the application.
+    Reject(StatusCode),
+    /// Leaves the request to the built-in logic of the middleware.
+    Continue,
+}
+
+/// Custom rules which decide what [`RedirectHTTPS`](crate::RedirectHTTPS) does with each
+/// request, set with [`RedirectHTTPS::policy`](crate::RedirectHTTPS::policy).
+///
+/// The policy runs before any of the built-in logic, including the exemptions, so it replaces
+/// that logic for every request it does not return [`RedirectDecision::Continue`] for.
+pub trait RedirectPolicy: Send + Sync {
+    /// Returns what to do with the request.
+    fn decide(&self, req: &ServiceRequest) -> RedirectDecision;
+}
+
+impl<P: RedirectPolicy + ?Sized> RedirectPolicy for Box<P> {
+    fn decide(&self, req: &ServiceRequest) -> RedirectDecision {
+        (**self).decide(req)
+    }
+}
+
+/// Like [`RedirectPolicy`], but decides asynchronously, so it can consult application state, a
+/// cache or a database without blocking the worker. Set it with
+/// [`RedirectHTTPS::async_policy`](crate::RedirectHTTPS::async_policy).
+///
+/// The future cannot borrow the request, so anything it needs, such as the host or a handle
+/// from the application data, should be taken from the request before it is created.
+pub trait AsyncRedirectPolicy: Send + Sync {
+    /// Returns a future which resolves to what to do with the request.
+    fn decide(&self, req: &ServiceRequest) -> LocalBoxFuture<'static, RedirectDecision>;
+}
+
+impl<P: AsyncRedirectPolicy + ?Sized> AsyncRedirectPolicy for Box<P> {
+    fn decide(&self, req: &ServiceRequest) -> LocalBoxFuture<'static, RedirectDecision> {
+        (**self).decide(req)
+    }
+}
@@ -0,0 +1,23 @@
+use actix_web::{dev::ServiceRequest, http::header, http::Method};
+
+/// How [`RedirectHTTPS::preflight`](crate::RedirectHTTPS::preflight) handles CORS preflight
+/// requests, which are `OPTIONS` requests with an `Origin` header. Browsers don't follow
+/// redirects on preflights, so a redirected preflight fails without a useful error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PreflightPolicy {
+    /// Redirects preflights like any other request.
+    #[default]
+    Redirect,
+    /// Passes preflights through to the application, for example to a CORS middleware.
+    PassThrough,
+    /// Responds to preflights with `403 Forbidden`.
+    Reject,
+    /// Responds to preflights with an empty `204 No Content`, granting no CORS permissions.
+    Respond,
+}
+
+/// Returns whether the request is a CORS preflight.
+pub(crate) fn is_preflight(req: &ServiceRequest) -> bool {
+    req.method() == Method::OPTIONS && req.headers().contains_key(header::ORIGIN)
+}
@@ -0,0 +1,51 @@
+use aho_corasick::{AhoCorasick, MatchKind};
+
+/// String replacements for the redirect url, compiled into a single automaton so that any number
+/// of them is applied in one pass over the url.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Replacements {
+    pairs: Vec<(String, String)>,
+    automaton: Option<AhoCorasick>,
+    /// The replacement for each pattern of the automaton, by pattern index.
+    to: Vec<String>,
+}
+
+impl Replacements {
+    pub(crate) fn new(pairs: &[(String, String)]) -> Self {
+        // Empty patterns would match between every character of the url.
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .filter(|(from, _)| !from.is_empty())
+            .cloned()
+            .collect();
+        let automaton = match pairs.is_empty() {
+            true => None,
+            false => AhoCorasick::builder()
+                .match_kind(MatchKind::LeftmostFirst)
+                .build(pairs.iter().map(|(from, _)| from))
+                .ok(),
+        };
+        let to = pairs.iter().map(|(_, to)| to.clone()).collect();
+        Replacements {
+            pairs,
+            automaton,
+            to,
+        }
+    }
+
+    /// Adds more replacements, which have lower priority than the existing ones.
+    pub(crate) fn extend(&mut self, pairs: &[(String, String)]) {
+        let mut all = std::mem::take(&mut self.pairs);
+        all.extend_from_slice(pairs);
+        *self = Self::new(&all);
+    }
+
+    /// Replaces every occurrence of every pattern. Where patterns overlap, the one added first
+    /// wins, and replaced text is not searched again.
+    pub(crate) fn apply(&self, url: String) -> String {
+        match &self.automaton {
+            Some(automaton) => automaton.replace_all(&url, &self.to),
+            None => url,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use actix_web::{dev::ServiceRequest, http::header};
+
+/// A url scheme for [`RedirectScheme::new`](crate::RedirectScheme::new).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Scheme {
+    Http,
+    Https,
+    /// WebSocket handshakes over `http`.
+    Ws,
+    /// WebSocket handshakes over `https`.
+    Wss,
+}
+
+impl Scheme {
+    /// The scheme as it appears in urls, such as `https`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::Ws => "ws",
+            Scheme::Wss => "wss",
+        }
+    }
+
+    /// Returns whether the scheme uses TLS.
+    pub fn is_secure(self) -> bool {
+        matches!(self, Scheme::Https | Scheme::Wss)
+    }
+
+    /// Returns whether the scheme is for WebSocket handshakes.
+    pub fn is_websocket(self) -> bool {
+        matches!(self, Scheme::Ws | Scheme::Wss)
+    }
+
+    /// The WebSocket scheme which uses TLS if this scheme does.
+    pub(crate) fn websocket(self) -> Self {
+        match self.is_secure() {
+            true => Scheme::Wss,
+            false => Scheme::Ws,
+        }
+    }
+
+    /// The port used when urls of this scheme do not specify one.
+    pub(crate) fn default_port(self) -> u16 {
+        match self.is_secure() {
+            true => 443,
+            false => 80,
+        }
+    }
+
+    /// Returns whether a request, which arrived over TLS if `secure`, used this scheme. Requests
+    /// which are not WebSocket handshakes never use `ws` or `wss`, while handshakes count as
+    /// `http` or `https` too.
+    pub(crate) fn matches(self, req: &ServiceRequest, secure: bool) -> bool {
+        secure == self.is_secure() && (!self.is_websocket() || is_websocket(req))
+    }
+}
+
+/// How [`RedirectHTTPS::websocket`](crate::RedirectHTTPS::websocket) handles WebSocket
+/// handshakes, which are requests with an `Upgrade: websocket` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum WebSocketPolicy {
+    /// Redirects handshakes like any other request, which many WebSocket clients won't follow.
+    #[default]
+    Redirect,
+    /// Redirects handshakes to `wss://` instead of `https://`.
+    Secure,
+    /// Responds to handshakes with `426 Upgrade Required` and a message explaining that the
+    /// connection must use `wss`.
+    Reject,
+}
+
+/// Returns whether the request is a WebSocket handshake.
+pub(crate) fn is_websocket(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get_all(header::UPGRADE)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim().eq_ignore_ascii_case("websocket"))
+}
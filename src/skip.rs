@@ -0,0 +1,53 @@
+use actix_web::{dev::ServiceRequest, HttpMessage};
+
+/// A marker which earlier middleware, such as authentication or internal routing, can insert
+/// into the extensions of a request so [`RedirectHTTPS`](crate::RedirectHTTPS) passes it
+/// through untouched.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{dev::Service, App, HttpMessage};
+/// use actix_web_middleware_redirect_https::{RedirectHTTPS, SkipHttpsRedirect};
+///
+/// App::new()
+///     .wrap(RedirectHTTPS::default())
+///     .wrap_fn(|req, srv| {
+///         if req.path().starts_with("/internal/") {
+///             req.extensions_mut().insert(SkipHttpsRedirect);
+///         }
+///         srv.call(req)
+///     });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SkipHttpsRedirect;
+
+/// Marks a request which instances of the middleware already handled. A nested instance, such as
+/// one on a scope, passes the request through if an instance with the same configuration handled
+/// it, such as the same middleware wrapped twice, but otherwise applies its own configuration.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Handled {
+    /// Whether the innermost instance found the request to be made over `https`, which guards
+    /// and extractors reuse so they agree with its trusted proxies.
+    pub(crate) secure: bool,
+    /// The addresses of the configurations which handled the request, which identify them
+    /// without keeping them alive.
+    configs: Vec<usize>,
+}
+
+impl Handled {
+    /// Returns whether an instance with the given configuration already handled the request.
+    pub(crate) fn contains(req: &ServiceRequest, config: usize) -> bool {
+        req.extensions()
+            .get::<Handled>()
+            .is_some_and(|handled| handled.configs.contains(&config))
+    }
+
+    /// Records that an instance with the given configuration handled the request.
+    pub(crate) fn insert(req: &ServiceRequest, config: usize, secure: bool) {
+        let mut extensions = req.extensions_mut();
+        let mut handled = extensions.remove::<Handled>().unwrap_or_default();
+        handled.secure = secure;
+        handled.configs.push(config);
+        extensions.insert(handled);
+    }
+}
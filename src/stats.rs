@@ -0,0 +1,137 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A cloneable set of counters of what the middleware did with `http` requests, for example to
+/// render from an existing `/metrics` endpoint. Attach it with
+/// [`RedirectHTTPS::stats`](crate::RedirectHTTPS::stats); all clones share the same counters.
+///
+/// ## Usage
+/// ```
+/// use actix_web::{App, web, HttpResponse};
+/// use actix_web_middleware_redirect_https::{RedirectHTTPS, RedirectStats};
+///
+/// let stats = RedirectStats::new();
+///
+/// App::new()
+///     .app_data(web::Data::new(stats.clone()))
+///     .wrap(RedirectHTTPS::default().stats(&stats))
+///     .route("/metrics", web::get().to(|stats: web::Data<RedirectStats>| async move {
+///         HttpResponse::Ok().content_type("text/plain").body(stats.prometheus())
+///     }));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RedirectStats {
+    counts: Arc<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    redirects: AtomicU64,
+    pass_throughs: AtomicU64,
+    exemptions: AtomicU64,
+    rejections: AtomicU64,
+}
+
+impl RedirectStats {
+    /// Creates a set of counters, all starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of requests which were redirected.
+    pub fn redirects(&self) -> u64 {
+        self.counts.redirects.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `http` requests which were passed on to the application, including
+    /// exempt ones.
+    pub fn pass_throughs(&self) -> u64 {
+        self.counts.pass_throughs.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `http` requests which were passed on because they were exempt.
+    pub fn exemptions(&self) -> u64 {
+        self.counts.exemptions.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `http` requests which got an error response instead of a redirect,
+    /// such as for an unsafe method or a disallowed host.
+    pub fn rejections(&self) -> u64 {
+        self.counts.rejections.load(Ordering::Relaxed)
+    }
+
+    /// Renders the counters in the Prometheus text format.
+    pub fn prometheus(&self) -> String {
+        let counters = [
+            (
+                "https_redirects_total",
+                "Requests redirected.",
+                self.redirects(),
+            ),
+            (
+                "http_passthroughs_total",
+                "Requests passed on over http.",
+                self.pass_throughs(),
+            ),
+            (
+                "http_exemptions_total",
+                "Exempt requests passed on over http.",
+                self.exemptions(),
+            ),
+            (
+                "http_rejections_total",
+                "Requests rejected over http.",
+                self.rejections(),
+            ),
+        ];
+        let mut text = String::new();
+        for (name, help, value) in counters {
+            let _ = write!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n",
+                name = name,
+                help = help,
+                value = value
+            );
+        }
+        text
+    }
+
+    /// Increments the counter of an event.
+    pub(crate) fn count(&self, event: Event) {
+        let counter = match event {
+            Event::Redirect => &self.counts.redirects,
+            Event::PassThrough => &self.counts.pass_throughs,
+            Event::Exemption => &self.counts.exemptions,
+            Event::Rejection => &self.counts.rejections,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Something the middleware did with a request, which is counted.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Event {
+    /// The request was redirected.
+    Redirect,
+    /// An `http` request was passed on to the application.
+    PassThrough,
+    /// An exempt `http` request was passed on, which is also a pass-through.
+    Exemption,
+    /// An `http` request got an error response instead of a redirect.
+    Rejection,
+}
+
+impl Event {
+    /// Returns the name of the counter of the event.
+    #[cfg_attr(not(feature = "statsd"), allow(dead_code))]
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Event::Redirect => "redirects",
+            Event::PassThrough => "pass_throughs",
+            Event::Exemption => "exemptions",
+            Event::Rejection => "rejections",
+        }
+    }
+}
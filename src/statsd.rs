@@ -0,0 +1,87 @@
+use crate::stats::Event;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A cloneable client which sends counters of what the middleware did with `http` requests, and
+/// how long each decision took, to a StatsD or DogStatsD server over UDP. Attach it with
+/// [`RedirectHTTPS::statsd`](crate::RedirectHTTPS::statsd).
+///
+/// Metrics are sent as `<prefix>.redirects`, `<prefix>.pass_throughs`, `<prefix>.exemptions`
+/// and `<prefix>.rejections` counters and a `<prefix>.decision` timer. Sending never blocks the
+/// worker, and metrics which cannot be sent are dropped.
+///
+/// ## Usage
+/// ```
+/// use actix_web::App;
+/// use actix_web_middleware_redirect_https::{RedirectHTTPS, StatsdClient};
+///
+/// let statsd = StatsdClient::new("127.0.0.1:8125", "redirect_https").unwrap();
+/// App::new()
+///     .wrap(RedirectHTTPS::default().statsd(&statsd));
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatsdClient {
+    socket: Arc<UdpSocket>,
+    prefix: Arc<str>,
+}
+
+impl StatsdClient {
+    /// Creates a client sending to the server at `addr`, with names starting with `prefix`.
+    ///
+    /// Fails if `addr` cannot be resolved or no socket can be bound.
+    pub fn new<A: ToSocketAddrs>(addr: A, prefix: &str) -> io::Result<Self> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to send metrics to")
+        })?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(StatsdClient {
+            socket: Arc::new(socket),
+            prefix: prefix.trim_end_matches('.').into(),
+        })
+    }
+
+    /// Increments the counter of an event.
+    pub(crate) fn count(&self, event: Event) {
+        self.send(&format!("{}.{}:1|c", self.prefix, event.name()));
+    }
+
+    /// Records how long the middleware took to decide what to do with a request.
+    pub(crate) fn time_decision(&self, elapsed: Duration) {
+        let millis = elapsed.as_secs_f64() * 1000.0;
+        self.send(&format!("{}.decision:{:.3}|ms", self.prefix, millis));
+    }
+
+    fn send(&self, metric: &str) {
+        let _ = self.socket.send(metric.as_bytes());
+    }
+}
+
+/// Measures a decision of the middleware, recording it with the client when dropped, so a
+/// decision which continues in a future is timed until the future has decided too.
+pub(crate) struct DecisionTimer {
+    client: StatsdClient,
+    start: Instant,
+}
+
+impl DecisionTimer {
+    pub(crate) fn start(client: &StatsdClient) -> Self {
+        DecisionTimer {
+            client: client.clone(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for DecisionTimer {
+    fn drop(&mut self) {
+        self.client.time_decision(self.start.elapsed());
+    }
+}
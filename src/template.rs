@@ -0,0 +1,111 @@
+use crate::ConfigError;
+use std::str::FromStr;
+
+/// A template for the redirect url, such as `https://{host}:{https_port}{path}{query}`, for
+/// declaratively controlling how the url is assembled. Set it with
+/// [`RedirectHTTPS::location_template`](crate::RedirectHTTPS::location_template).
+///
+/// The placeholders are:
+///
+/// - `{host}`: the host name, without the port.
+/// - `{https_port}`: the port after any port mappings, `443` by default for `https`.
+/// - `{authority}`: the host name with the `https` port, if it is not the default.
+/// - `{path}`: the path, including any path prefix.
+/// - `{query}`: the query string with a leading `?`, or nothing if the url has none.
+///
+/// ## Usage
+/// ```
+/// use actix_web_middleware_redirect_https::LocationTemplate;
+///
+/// let template: LocationTemplate = "https://{host}:{https_port}/app{path}{query}".parse().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Host,
+    HttpsPort,
+    Authority,
+    Path,
+    Query,
+}
+
+/// The values the placeholders of a [`LocationTemplate`] are replaced with.
+pub(crate) struct LocationParts<'a> {
+    pub(crate) host: &'a str,
+    pub(crate) https_port: Option<&'a str>,
+    /// The default port of the scheme redirected to.
+    pub(crate) default_port: &'a str,
+    pub(crate) path: &'a str,
+    pub(crate) query: Option<&'a str>,
+}
+
+impl LocationTemplate {
+    /// Parses a template, returning an error for unknown or unclosed placeholders.
+    pub fn new(template: &str) -> Result<Self, ConfigError> {
+        let invalid = || ConfigError::InvalidTemplate(template.to_owned());
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let end = rest[start..].find('}').ok_or_else(invalid)? + start;
+            parts.push(match &rest[start + 1..end] {
+                "host" => Part::Host,
+                "https_port" => Part::HttpsPort,
+                "authority" => Part::Authority,
+                "path" => Part::Path,
+                "query" => Part::Query,
+                _ => return Err(invalid()),
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+        Ok(LocationTemplate { parts })
+    }
+
+    /// Assembles the url.
+    pub(crate) fn render(&self, location: &LocationParts) -> String {
+        let mut url = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => url.push_str(literal),
+                Part::Host => url.push_str(location.host),
+                Part::HttpsPort => {
+                    url.push_str(location.https_port.unwrap_or(location.default_port))
+                }
+                Part::Authority => {
+                    url.push_str(location.host);
+                    let port = location.https_port;
+                    if let Some(port) = port.filter(|port| *port != location.default_port) {
+                        url.push(':');
+                        url.push_str(port);
+                    }
+                }
+                Part::Path => url.push_str(location.path),
+                Part::Query => {
+                    if let Some(query) = location.query {
+                        url.push('?');
+                        url.push_str(query);
+                    }
+                }
+            }
+        }
+        url
+    }
+}
+
+impl FromStr for LocationTemplate {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
@@ -0,0 +1,56 @@
+//! Spans around the redirect decision for the `tracing` crate.
+
+use crate::forwarded::Forwarding;
+use actix_web::{dev::ServiceRequest, http::header, HttpResponse};
+use tracing::{field, Span};
+
+/// The name and target of the span.
+const NAME: &str = "redirect_https";
+
+/// Creates the span of the decision about a request, with the source which decided its scheme.
+/// The decision and its target are recorded once they are known.
+pub(crate) fn span(req: &ServiceRequest, forwarding: &Forwarding) -> Span {
+    tracing::debug_span!(
+        target: NAME,
+        NAME,
+        scheme_source = forwarding.scheme_source(req),
+        decision = field::Empty,
+        status = field::Empty,
+        target = field::Empty,
+    )
+}
+
+/// Records the decision about the request, such as `pass` if it was passed on to the application.
+pub(crate) fn record(decision: &str) {
+    if let Some(span) = current() {
+        span.record("decision", decision);
+    }
+}
+
+/// Records the response the middleware sent instead of passing the request on, and the
+/// `Location` it points to, if any.
+pub(crate) fn record_response(res: &HttpResponse) {
+    let span = match current() {
+        Some(span) => span,
+        None => return,
+    };
+    let decision = match res.status().is_redirection() {
+        true => "redirect",
+        false => "respond",
+    };
+    span.record("decision", decision);
+    span.record("status", res.status().as_u16());
+    let location = res.headers().get(header::LOCATION);
+    if let Some(location) = location.and_then(|location| location.to_str().ok()) {
+        span.record("target", location);
+    }
+}
+
+/// Returns the current span if it is the span of a decision.
+fn current() -> Option<Span> {
+    let span = Span::current();
+    let is_decision = span
+        .metadata()
+        .is_some_and(|metadata| metadata.name() == NAME && metadata.target() == NAME);
+    is_decision.then_some(span)
+}
@@ -0,0 +1,44 @@
+#[cfg(feature = "serde")]
+use crate::RedirectConfig;
+use crate::{Options, RedirectHTTPS};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// A channel new configurations for [`RedirectHTTPS::from_watch`] or
+/// [`RedirectHTTPS::from_config_watch`] arrive on.
+#[derive(Clone)]
+pub(crate) enum Updates {
+    Middleware(watch::Receiver<RedirectHTTPS>),
+    #[cfg(feature = "serde")]
+    Config(watch::Receiver<RedirectConfig>),
+}
+
+impl Updates {
+    /// Returns the options of the configuration sent since the last call, if any. A
+    /// [`RedirectConfig`] which does not build is skipped, so the current options stay in place.
+    pub(crate) fn changed(&mut self) -> Option<Arc<Options>> {
+        match self {
+            Updates::Middleware(updates) => {
+                if !updates.has_changed().unwrap_or(false) {
+                    return None;
+                }
+                Some(updates.borrow_and_update().options.clone())
+            }
+            #[cfg(feature = "serde")]
+            Updates::Config(updates) => {
+                if !updates.has_changed().unwrap_or(false) {
+                    return None;
+                }
+                let config = updates.borrow_and_update().clone();
+                match config.build() {
+                    Ok(config) => Some(config.options),
+                    Err(_error) => {
+                        #[cfg(feature = "logging")]
+                        crate::logging::invalid_config(&_error);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
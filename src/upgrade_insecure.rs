@@ -0,0 +1,91 @@
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    Error,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::task::{Context, Poll};
+
+const DIRECTIVE: &str = "upgrade-insecure-requests";
+
+/// Middleware for `actix-web` which adds `Content-Security-Policy: upgrade-insecure-requests` to
+/// responses for requests which arrived over `https`, so browsers also load mixed-content
+/// subresources, such as images and scripts linked with `http` urls, over `https`.
+///
+/// The policy is added as a separate `Content-Security-Policy` header, which browsers enforce
+/// alongside any policy the application sets, unless the application's policy already includes
+/// the directive. Whether a request arrived over `https` is decided by actix's `ConnectionInfo`,
+/// which believes the `Forwarded` and `X-Forwarded-Proto` headers.
+///
+/// ## Usage
+/// ```
+/// use actix_web::App;
+/// use actix_web_middleware_redirect_https::{RedirectHTTPS, UpgradeInsecureRequests};
+///
+/// App::new()
+///     .wrap(UpgradeInsecureRequests)
+///     .wrap(RedirectHTTPS::default());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct UpgradeInsecureRequests;
+
+impl<S, B> Transform<S, ServiceRequest> for UpgradeInsecureRequests
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = UpgradeInsecureRequestsService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(UpgradeInsecureRequestsService { service })
+    }
+}
+
+pub struct UpgradeInsecureRequestsService<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for UpgradeInsecureRequestsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let secure = req.connection_info().scheme() == "https";
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if secure && !has_directive(&res) {
+                res.headers_mut().append(
+                    header::CONTENT_SECURITY_POLICY,
+                    HeaderValue::from_static(DIRECTIVE),
+                );
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Returns whether a policy of the response already upgrades insecure requests.
+fn has_directive<B>(res: &ServiceResponse<B>) -> bool {
+    res.headers()
+        .get_all(header::CONTENT_SECURITY_POLICY)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|policy| policy.split(';'))
+        .any(|directive| directive.trim().eq_ignore_ascii_case(DIRECTIVE))
+}
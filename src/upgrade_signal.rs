@@ -0,0 +1,24 @@
+use actix_web::{dev::ServiceRequest, http::header};
+
+/// How [`RedirectHTTPS::missing_upgrade_signal`](crate::RedirectHTTPS::missing_upgrade_signal)
+/// handles `http` requests without `Upgrade-Insecure-Requests: 1`, which browsers send to tell
+/// the server they prefer `https`. Other clients, such as old scripts and devices, may not cope
+/// with a redirect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum UpgradeSignalPolicy {
+    /// Redirects requests whether or not they send the header.
+    #[default]
+    Redirect,
+    /// Passes requests without the header through to the application over `http`.
+    PassThrough,
+    /// Responds to requests without the header with `403 Forbidden`.
+    Reject,
+}
+
+/// Returns whether the request asks to be upgraded to `https`.
+pub(crate) fn sends_upgrade_signal(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(header::UPGRADE_INSECURE_REQUESTS)
+        .is_some_and(|value| value.as_bytes().trim_ascii() == b"1")
+}
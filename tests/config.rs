@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+use actix_web_middleware_redirect_https::{ConfigError, RedirectConfig, RedirectHTTPS};
+
+fn from_json(json: &str) -> Result<RedirectHTTPS, ConfigError> {
+    RedirectHTTPS::from_config(serde_json::from_str::<RedirectConfig>(json).unwrap())
+}
+
+#[test]
+fn hsts_directives_require_a_max_age() {
+    for (json, name) in [
+        (r#"{"hsts_preload": true}"#, "hsts_preload"),
+        (
+            r#"{"hsts_include_subdomains": true}"#,
+            "hsts_include_subdomains",
+        ),
+    ] {
+        assert_eq!(
+            from_json(json).err(),
+            Some(ConfigError::InvalidValue {
+                name: name.to_owned(),
+                value: "true".to_owned(),
+            })
+        );
+    }
+    assert!(from_json(r#"{"hsts_preload": false}"#).is_ok());
+    assert!(from_json(r#"{"hsts_max_age": 300, "hsts_include_subdomains": true}"#).is_ok());
+}
+
+#[cfg(feature = "tokio")]
+#[actix_web::test]
+async fn follows_the_configurations_sent_on_a_channel() {
+    use actix_web::{
+        http::{header, StatusCode},
+        test, web, App, HttpResponse,
+    };
+    use tokio::sync::watch;
+
+    let config = |json: &str| serde_json::from_str::<RedirectConfig>(json).unwrap();
+    let (_, receiver) = watch::channel(config(r#"{"status_code": 200}"#));
+    assert_eq!(
+        RedirectHTTPS::from_config_watch(receiver).err(),
+        Some(ConfigError::InvalidStatusCode(StatusCode::OK))
+    );
+
+    let (updates, receiver) = watch::channel(config("{}"));
+    let app = test::init_service(
+        App::new()
+            .wrap(RedirectHTTPS::from_config_watch(receiver).unwrap())
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    let status = || async {
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::HOST, "example.com"))
+            .to_request();
+        test::call_service(&app, req).await.status()
+    };
+    assert_eq!(status().await, StatusCode::MOVED_PERMANENTLY);
+    updates.send(config(r#"{"status_code": 308}"#)).unwrap();
+    assert_eq!(status().await, StatusCode::PERMANENT_REDIRECT);
+    // An invalid configuration keeps the previous one.
+    updates.send(config(r#"{"status_code": 200}"#)).unwrap();
+    assert_eq!(status().await, StatusCode::PERMANENT_REDIRECT);
+    updates.send(config(r#"{"enabled": false}"#)).unwrap();
+    assert_eq!(status().await, StatusCode::OK);
+}
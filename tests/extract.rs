@@ -0,0 +1,53 @@
+use actix_web::{
+    http::{header, StatusCode},
+    test, web, App, HttpResponse,
+};
+use actix_web_middleware_redirect_https::{ConfigError, RequireHttps, RequireHttpsConfig};
+
+async fn handler(_: RequireHttps) -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+#[actix_web::test]
+async fn forwarded_headers_do_not_satisfy_the_extractor_alone() {
+    let app = test::init_service(App::new().route("/", web::get().to(handler))).await;
+    let req = test::TestRequest::get()
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn redirect_uses_the_configured_host() {
+    let config = RequireHttpsConfig::default()
+        .redirect("example.com", StatusCode::FOUND)
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(config)
+            .route("/a", web::get().to(handler)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a?b=1")
+        .insert_header((header::HOST, "evil.example"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "https://example.com/a?b=1"
+    );
+}
+
+#[actix_web::test]
+async fn redirect_rejects_invalid_options() {
+    let config = RequireHttpsConfig::default().redirect("example.com", StatusCode::OK);
+    assert_eq!(
+        config.err(),
+        Some(ConfigError::InvalidStatusCode(StatusCode::OK))
+    );
+    let config = RequireHttpsConfig::default().redirect("user@example.com", StatusCode::FOUND);
+    assert!(matches!(config, Err(ConfigError::InvalidValue { .. })));
+}
@@ -0,0 +1,124 @@
+use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+use actix_web_middleware_redirect_https::{guard::HttpsOnly, RedirectControl, RedirectHTTPS};
+
+#[actix_web::test]
+async fn spoofed_header_does_not_pass_without_the_middleware() {
+    let app = test::init_service(
+        App::new()
+            .route(
+                "/",
+                web::get()
+                    .guard(HttpsOnly::new())
+                    .to(|| async { HttpResponse::Ok().finish() }),
+            )
+            .default_service(web::to(|| async { HttpResponse::NotFound().finish() })),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn forwarded_headers_are_believed_when_trusted() {
+    let app = test::init_service(
+        App::new().route(
+            "/",
+            web::get()
+                .guard(HttpsOnly::trust_forwarded())
+                .to(|| async { HttpResponse::Ok().finish() }),
+        ),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .insert_header(("forwarded", "for=192.0.2.1;proto=https"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn guard_agrees_with_the_middleware() {
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                RedirectHTTPS::default()
+                    .trusted_proxies(&["10.0.0.0/8".parse().unwrap()])
+                    .exclude_paths(&["/"]),
+            )
+            .route(
+                "/",
+                web::get()
+                    .guard(HttpsOnly::new())
+                    .to(|| async { HttpResponse::Ok().finish() }),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .peer_addr("10.0.0.1:1234".parse().unwrap())
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .peer_addr("203.0.113.1:1234".parse().unwrap())
+        .insert_header(("x-forwarded-proto", "https"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn guard_still_matches_while_the_middleware_is_disabled() {
+    for middleware in [
+        RedirectHTTPS::default().set_enabled(false),
+        RedirectHTTPS::default().control(&RedirectControl::new(false)),
+    ] {
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware.trusted_proxies(&["10.0.0.0/8".parse().unwrap()]))
+                .route(
+                    "/",
+                    web::get()
+                        .guard(HttpsOnly::new())
+                        .to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header(("x-forwarded-proto", "https"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[actix_web::test]
+async fn https_target_matches_without_the_middleware() {
+    let app = test::init_service(
+        App::new().route(
+            "/",
+            web::get()
+                .guard(HttpsOnly::new())
+                .to(|| async { HttpResponse::Ok().finish() }),
+        ),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("https://example.com/")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri("http://example.com/")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
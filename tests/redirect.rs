@@ -0,0 +1,311 @@
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, StatusCode},
+    test, web, App, HttpResponse,
+};
+use actix_web_middleware_redirect_https::{
+    ConfigError, Hsts, RedirectDecision, RedirectHTTPS, RedirectPolicy, RedirectScheme,
+    RedirectStats, Scheme,
+};
+
+async fn call(middleware: RedirectHTTPS, req: test::TestRequest) -> ServiceResponse {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware)
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    test::call_service(&app, req.to_request())
+        .await
+        .map_into_boxed_body()
+}
+
+fn location(res: &ServiceResponse) -> Option<&str> {
+    res.headers()
+        .get(header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[actix_web::test]
+async fn absolute_form_https_target_over_http_is_redirected() {
+    let middleware = RedirectHTTPS::default().trusted_proxies(&["10.0.0.0/8".parse().unwrap()]);
+    let req = test::TestRequest::get()
+        .uri("https://example.com/a")
+        .peer_addr("203.0.113.1:1234".parse().unwrap())
+        .insert_header((header::HOST, "example.com"));
+    let res = call(middleware, req).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location(&res), Some("https://example.com/a"));
+}
+
+#[actix_web::test]
+async fn nested_instances_for_other_schemes_still_redirect() {
+    let app = test::init_service(
+        App::new()
+            .wrap(RedirectHTTPS::default())
+            .wrap(RedirectScheme::new(Scheme::Ws, Scheme::Wss))
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a")
+        .insert_header((header::HOST, "example.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "https://example.com/a"
+    );
+}
+
+#[actix_web::test]
+async fn nested_scope_instance_applies_its_own_configuration() {
+    let app = test::init_service(
+        App::new()
+            .wrap(RedirectHTTPS::default().exclude_paths(&["/api"]))
+            .service(
+                web::scope("/api")
+                    .wrap(RedirectHTTPS::default().status_code(StatusCode::PERMANENT_REDIRECT))
+                    .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/api/a")
+        .insert_header((header::HOST, "example.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+}
+
+#[actix_web::test]
+async fn same_configuration_wrapped_twice_handles_requests_once() {
+    let stats = RedirectStats::new();
+    let middleware = RedirectHTTPS::default()
+        .exclude_paths(&["/healthz"])
+        .stats(&stats);
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware.clone())
+            .wrap(middleware)
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/healthz")
+        .insert_header((header::HOST, "example.com"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(stats.exemptions(), 1);
+}
+
+#[actix_web::test]
+async fn scope_redirects_while_the_rest_is_disabled() {
+    let middleware = RedirectHTTPS::default()
+        .set_enabled(false)
+        .scope("/secure", RedirectHTTPS::default());
+    let req = test::TestRequest::get()
+        .uri("/secure/a")
+        .insert_header((header::HOST, "example.com"));
+    let res = call(middleware.clone(), req).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    let req = test::TestRequest::get()
+        .uri("/a")
+        .insert_header((header::HOST, "example.com"));
+    assert_eq!(call(middleware, req).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn unsafe_method_with_disallowed_host_is_not_reflected() {
+    let middleware = RedirectHTTPS::default()
+        .reject_unsafe_methods(StatusCode::FORBIDDEN)
+        .allowed_hosts(&["example.com"]);
+    let req = test::TestRequest::post()
+        .uri("/a")
+        .insert_header((header::HOST, "evil.example"));
+    let res = call(middleware, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = test::read_body(res).await;
+    assert!(!String::from_utf8_lossy(&body).contains("evil.example"));
+}
+
+#[actix_web::test]
+async fn location_cache_keeps_requests_apart() {
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                RedirectHTTPS::default()
+                    .location_cache(16)
+                    .scope("/api", RedirectHTTPS::default().preserve_query(false)),
+            )
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    for (host, uri, expected) in [
+        ("example.com", "/a?x=1", "https://example.com/a?x=1"),
+        ("example.org", "/a?x=1", "https://example.org/a?x=1"),
+        ("example.com", "/a?x=2", "https://example.com/a?x=2"),
+        ("example.com", "/api?x=1", "https://example.com/api"),
+        ("example.com", "/a?x=1", "https://example.com/a?x=1"),
+        ("example.com", "/api?x=1", "https://example.com/api"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header((header::HOST, host))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            expected,
+            "{} {}",
+            host,
+            uri
+        );
+    }
+}
+
+#[actix_web::test]
+async fn recommended_uses_the_value_of_the_outermost_trusted_proxy() {
+    // The client sent `X-Forwarded-Proto: https` itself, and the proxy appended the real scheme.
+    let req = test::TestRequest::get()
+        .uri("/a")
+        .peer_addr("10.0.0.1:1234".parse().unwrap())
+        .insert_header((header::HOST, "example.com"))
+        .insert_header(("X-Forwarded-For", "203.0.113.1"))
+        .insert_header(("X-Forwarded-Proto", "https, http"));
+    let res = call(RedirectHTTPS::recommended(), req).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location(&res), Some("https://example.com/a"));
+
+    let req = test::TestRequest::get()
+        .uri("/a")
+        .peer_addr("10.0.0.1:1234".parse().unwrap())
+        .insert_header((header::HOST, "example.com"))
+        .insert_header(("X-Forwarded-For", "203.0.113.1"))
+        .insert_header(("X-Forwarded-Proto", "http, https"));
+    let res = call(RedirectHTTPS::recommended(), req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn invalid_options_are_reported_instead_of_panicking() {
+    let builder = RedirectHTTPS::builder;
+    assert_eq!(
+        builder().status_code(StatusCode::OK).build().err(),
+        Some(ConfigError::InvalidStatusCode(StatusCode::OK))
+    );
+    assert_eq!(
+        builder().schemes(Scheme::Http, Scheme::Http).build().err(),
+        Some(ConfigError::SameScheme(Scheme::Http))
+    );
+    assert_eq!(
+        builder()
+            .reject_unsafe_methods(StatusCode::FOUND)
+            .build()
+            .err(),
+        Some(ConfigError::InvalidRejectStatusCode(StatusCode::FOUND))
+    );
+    let _ = RedirectHTTPS::default().status_code(StatusCode::OK);
+    let _ = RedirectScheme::new(Scheme::Wss, Scheme::Wss);
+}
+
+#[actix_web::test]
+async fn validation_covers_scopes_and_hsts_paths() {
+    use std::time::Duration;
+
+    let scoped = RedirectHTTPS::builder().scope(
+        "/api",
+        RedirectHTTPS::default().schemes(Scheme::Https, Scheme::Https),
+    );
+    assert_eq!(
+        scoped.build().err(),
+        Some(ConfigError::SameScheme(Scheme::Https))
+    );
+    let hsts = Hsts::new(Duration::from_secs(63072000)).path(
+        "/beta",
+        Some(Hsts::new(Duration::from_secs(300)).preload(true)),
+    );
+    assert_eq!(
+        RedirectHTTPS::builder().hsts_policy(hsts).build().err(),
+        Some(ConfigError::HstsPreloadMaxAge(Duration::from_secs(300)))
+    );
+}
+
+#[actix_web::test]
+async fn spoofed_forwarded_for_does_not_match_exempt_peers() {
+    let middleware = RedirectHTTPS::default()
+        .trusted_proxies(&["10.0.0.0/8".parse().unwrap()])
+        .exempt_peers(&["127.0.0.0/8".parse().unwrap()]);
+    let req = test::TestRequest::get()
+        .uri("/a")
+        .peer_addr("10.0.0.1:1234".parse().unwrap())
+        .insert_header((header::HOST, "example.com"))
+        .insert_header(("X-Forwarded-For", "127.0.0.1, 203.0.113.1"));
+    let res = call(middleware, req).await;
+    assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+}
+
+#[actix_web::test]
+async fn redirect_expires_applies_in_either_order() {
+    use std::time::Duration;
+
+    let max_age = Duration::from_secs(86400);
+    let middlewares = [
+        RedirectHTTPS::default()
+            .redirect_cache(max_age)
+            .redirect_expires(true),
+        RedirectHTTPS::default()
+            .redirect_expires(true)
+            .redirect_cache(max_age),
+    ];
+    for middleware in middlewares {
+        let req = test::TestRequest::get().insert_header((header::HOST, "example.com"));
+        let res = call(middleware, req).await;
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=86400"
+        );
+        assert!(res.headers().contains_key(header::EXPIRES));
+    }
+    let req = test::TestRequest::get().insert_header((header::HOST, "example.com"));
+    let res = call(RedirectHTTPS::default().redirect_expires(true), req).await;
+    assert!(!res.headers().contains_key(header::EXPIRES));
+}
+
+#[actix_web::test]
+async fn policy_pass_over_https_adds_hsts() {
+    use std::time::Duration;
+
+    struct PassAll;
+
+    impl RedirectPolicy for PassAll {
+        fn decide(&self, _: &ServiceRequest) -> RedirectDecision {
+            RedirectDecision::Pass
+        }
+    }
+
+    let middleware = || {
+        RedirectHTTPS::default()
+            .hsts_policy(Hsts::new(Duration::from_secs(300)))
+            .policy(PassAll)
+    };
+    let req = test::TestRequest::get()
+        .uri("https://example.com/")
+        .insert_header((header::HOST, "example.com"));
+    let res = call(middleware(), req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .unwrap(),
+        "max-age=300"
+    );
+    let req = test::TestRequest::get().insert_header((header::HOST, "example.com"));
+    let res = call(middleware(), req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!res
+        .headers()
+        .contains_key(header::STRICT_TRANSPORT_SECURITY));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "statsd")]
+
+use actix_web::{dev::ServiceRequest, http::header, test, web, App, HttpResponse};
+use actix_web_middleware_redirect_https::{
+    AsyncRedirectPolicy, RedirectDecision, RedirectHTTPS, StatsdClient,
+};
+use futures::future::{FutureExt, LocalBoxFuture};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+struct Slow;
+
+impl AsyncRedirectPolicy for Slow {
+    fn decide(&self, _: &ServiceRequest) -> LocalBoxFuture<'static, RedirectDecision> {
+        async {
+            actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+            RedirectDecision::Continue
+        }
+        .boxed_local()
+    }
+}
+
+#[actix_web::test]
+async fn async_policy_decisions_are_timed() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let statsd = StatsdClient::new(server.local_addr().unwrap(), "redirect").unwrap();
+    let app = test::init_service(
+        App::new()
+            .wrap(RedirectHTTPS::default().statsd(&statsd).async_policy(Slow))
+            .default_service(web::to(|| async { HttpResponse::Ok().finish() })),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((header::HOST, "example.com"))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let mut buf = [0; 512];
+    let millis = loop {
+        let len = server.recv(&mut buf).unwrap();
+        let metric = std::str::from_utf8(&buf[..len]).unwrap();
+        if let Some(timing) = metric.strip_prefix("redirect.decision:") {
+            break timing.trim_end_matches("|ms").parse::<f64>().unwrap();
+        }
+    };
+    assert!(millis >= 50.0, "{}", millis);
+}